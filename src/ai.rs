@@ -1,3 +1,17 @@
+pub(crate) mod adaptive;
+pub(crate) mod explain;
+pub(crate) mod mcts;
+pub(crate) mod weights;
+
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+
 use crate::{FIELD_WIDTH_BLOCKS, MINIMUM_SEQUENCE};
 use crate::model::Field;
 
@@ -9,6 +23,124 @@ pub struct BestMove {
 }
 
 
+/// The strategy used by the AI to pick a move.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, ValueEnum)]
+pub(crate) enum AiStrategy {
+    /// Rates the field after each possible move and picks the best one.
+    #[default] Greedy,
+
+    /// Plays out random future pieces after each possible move and picks the one with the best
+    /// average outcome.
+    Mcts,
+
+    /// Like greedy, but prioritizes keeping the towers low over scoring once they get too high.
+    Adaptive,
+}
+
+
+/// The names of the criteria returned by [`rate_field`], in order of importance.
+pub(crate) const CRITERIA_NAMES: [&str; 3] = [
+    "score",
+    "extensible_sequences",
+    "negative_tower_height",
+];
+
+
+/// The maximum number of ratings kept in a transposition table before it is cleared.
+const TRANSPOSITION_TABLE_CAPACITY: usize = 1 << 16;
+
+
+/// The ratings shared by all searches for the best move, so that e.g. explaining a move and then
+/// picking it doesn't rate the same fields twice.
+static SHARED_TRANSPOSITION_TABLE: Lazy<Mutex<TranspositionTable>> = Lazy::new(|| Mutex::new(TranspositionTable::new()));
+
+
+/// Caches the results of [`rate_field`] by the Zobrist hash of the rated field.
+///
+/// Different rotations and columns frequently lead to identical fields, especially when multiple
+/// descending blocks share the same color. The rated field is stored along with its rating so that
+/// a different field with the same hash is never mistaken for it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TranspositionTable {
+    ratings: HashMap<u64, (Field, Vec<i64>)>,
+}
+impl TranspositionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the rating of the field if it has been stored before.
+    pub fn get(&self, field: &Field) -> Option<&Vec<i64>> {
+        self.ratings.get(&field.zobrist_hash())
+            .filter(|(stored_field, _rating)| stored_field == field)
+            .map(|(_stored_field, rating)| rating)
+    }
+
+    /// Stores the rating of the field, replacing any other field with the same hash.
+    pub fn insert(&mut self, field: &Field, rating: Vec<i64>) {
+        if self.ratings.len() >= TRANSPOSITION_TABLE_CAPACITY {
+            self.ratings.clear();
+        }
+        self.ratings.insert(field.zobrist_hash(), (field.clone(), rating));
+    }
+
+    /// Returns the rating of the field, rating it only if it has not been encountered before.
+    pub fn rate_field(&mut self, field: &Field) -> Vec<i64> {
+        if let Some(rating) = self.get(field) {
+            return rating.clone();
+        }
+
+        let rating = rate_field(field);
+        self.insert(field, rating.clone());
+        rating
+    }
+}
+
+
+/// Returns the number of threads to use for evaluating moves.
+///
+/// A requested count of 0 means one thread per available CPU.
+pub(crate) fn effective_thread_count(requested: usize) -> usize {
+    if requested > 0 {
+        requested
+    } else {
+        thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1)
+    }
+}
+
+
+/// Applies the function to every item, spreading the work across the given number of threads.
+///
+/// The results are returned in the same order as the items, regardless of which thread finishes
+/// first.
+pub(crate) fn parallel_map<T, R, F>(items: &[T], thread_count: usize, f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+{
+    if thread_count <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let chunk_size = items.len().div_ceil(thread_count);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+
+        // join in order of the chunks, not in order of completion
+        handles.into_iter()
+            .flat_map(|handle| handle.join().expect("AI worker thread panicked"))
+            .collect()
+    })
+}
+
+
 fn rotate_descending_blocks(field: &mut Field, count: usize) {
     for _ in 0..count {
         field.rotate_descending_blocks();
@@ -17,7 +149,28 @@ fn rotate_descending_blocks(field: &mut Field, count: usize) {
 
 
 
-fn rate_field(field: &Field) -> Vec<i64> {
+/// Removes all scoring sequences from the field, including those that form in the resulting
+/// cascade, and returns the score gained.
+pub(crate) fn settle_field(field: &mut Field) -> u64 {
+    settle_field_with_chain_depth(field).0
+}
+
+
+/// Like [`settle_field`], but also returns the chain depth, i.e. how many times in succession
+/// scoring sequences were removed.
+pub(crate) fn settle_field_with_chain_depth(field: &mut Field) -> (u64, u32) {
+    let mut score = 0;
+    let mut chain_depth = 0;
+    while field.disappear_scoring_sequences(&mut score) {
+        chain_depth += 1;
+        field.immediately_remove_disappearing_blocks();
+        field.immediately_drop_gravity_blocks();
+    }
+    (score, chain_depth)
+}
+
+
+pub(crate) fn rate_field(field: &Field) -> Vec<i64> {
     let mut criteria: Vec<i64> = Vec::new();
 
     // the first criterion is the score
@@ -27,10 +180,7 @@ fn rate_field(field: &Field) -> Vec<i64> {
     if scoring_sequences.len() > 0 {
         // simulate what this would do
         let mut scoring_field = field.clone();
-        while scoring_field.disappear_scoring_sequences(&mut field_score) {
-            scoring_field.immediately_remove_disappearing_blocks();
-            scoring_field.immediately_drop_gravity_blocks();
-        }
+        field_score = settle_field(&mut scoring_field);
     }
     criteria.push(field_score.try_into().unwrap());
 
@@ -55,14 +205,18 @@ fn rate_field(field: &Field) -> Vec<i64> {
 }
 
 
-pub(crate) fn pick_best_move(base_field: &Field) -> Option<BestMove> {
+/// Returns every possible move for the descending blocks along with the field that results once the
+/// blocks have been dropped.
+///
+/// Scoring sequences in the resulting field have not been removed yet.
+pub(crate) fn candidate_moves(base_field: &Field) -> Vec<(Field, BestMove)> {
     let desc_blocks = base_field
         .block_coords_with_predicate(|b| b.is_descending());
     if desc_blocks.len() == 0 {
-        return None;
+        return Vec::new();
     }
 
-    let mut fields_ratings = Vec::new();
+    let mut candidates = Vec::new();
     for rotate_count in 0..desc_blocks.len() {
         let mut rotated_field = base_field.clone();
         rotate_descending_blocks(&mut rotated_field, rotate_count);
@@ -93,22 +247,102 @@ pub(crate) fn pick_best_move(base_field: &Field) -> Option<BestMove> {
             columned_field.hand_descending_blocks_to_gravity();
             columned_field.immediately_drop_gravity_blocks();
 
-            // how good is this state?
-            let rating = rate_field(&columned_field);
-
-            fields_ratings.push((
-                columned_field, 
+            candidates.push((
+                columned_field,
                 BestMove {
                     column,
                     rotate_count,
                 },
-                rating
             ));
         }
     }
 
+    candidates
+}
+
+
+/// Returns every possible move for the descending blocks along with the resulting field's rating,
+/// in a fixed order.
+pub(crate) fn rate_candidates(base_field: &Field, thread_count: usize) -> Vec<(BestMove, Vec<i64>)> {
+    let candidates = candidate_moves(base_field);
+
+    // look up the fields that have been rated before
+    let known_ratings: Vec<Option<Vec<i64>>> = {
+        let table = SHARED_TRANSPOSITION_TABLE.lock().unwrap();
+        candidates.iter()
+            .map(|(field, _best_move)| table.get(field).cloned())
+            .collect()
+    };
+
+    // how good is each state? (only rate each distinct field once)
+    let mut unrated_fields: Vec<&Field> = Vec::new();
+    for ((field, _best_move), known_rating) in candidates.iter().zip(&known_ratings) {
+        if known_rating.is_none() && !unrated_fields.contains(&field) {
+            unrated_fields.push(field);
+        }
+    }
+    let new_ratings = parallel_map(&unrated_fields, thread_count, |field| rate_field(field));
+    {
+        let mut table = SHARED_TRANSPOSITION_TABLE.lock().unwrap();
+        for (field, rating) in unrated_fields.iter().zip(&new_ratings) {
+            table.insert(field, rating.clone());
+        }
+    }
+
+    candidates.iter()
+        .zip(known_ratings)
+        .map(|((field, best_move), known_rating)| {
+            let rating = known_rating.unwrap_or_else(|| {
+                let unrated_index = unrated_fields.iter().position(|unrated| *unrated == field).unwrap();
+                new_ratings[unrated_index].clone()
+            });
+            (*best_move, rating)
+        })
+        .collect()
+}
+
+
+pub(crate) fn pick_best_move(base_field: &Field, thread_count: usize) -> Option<BestMove> {
     // pick the best field by rating
-    fields_ratings.into_iter()
-        .max_by_key(|(_field, _best_move, rating)| rating.clone())
-        .map(|(_field, best_move, _rating)| best_move)
+    // (ratings are in candidate order, so the last of equally-rated candidates always wins)
+    rate_candidates(base_field, thread_count)
+        .into_iter()
+        .max_by_key(|(_best_move, rating)| rating.clone())
+        .map(|(best_move, _rating)| best_move)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{TranspositionTable, rate_candidates, rate_field};
+    use crate::game::Game;
+    use crate::model::{Block, BlockState, FieldBlock};
+
+    #[test]
+    fn test_rate_candidates() {
+        let mut game = Game::new(0);
+        game.run_until_next_piece();
+        *game.field.block_by_coord_mut(0, 17) = FieldBlock::Block(Block {
+            color_index: 1,
+            state: BlockState::Stationary,
+        });
+
+        // the ratings are the same whether they are calculated or looked up
+        let first = rate_candidates(&game.field, 2);
+        let second = rate_candidates(&game.field, 1);
+        assert_eq!(first, second);
+        assert!(!first.is_empty());
+
+        // a field is only found in the table if it matches the stored one exactly
+        let mut table = TranspositionTable::new();
+        let rating = table.rate_field(&game.field);
+        assert_eq!(table.get(&game.field), Some(&rating));
+        assert_eq!(rating, rate_field(&game.field));
+        let mut other_field = game.field.clone();
+        *other_field.block_by_coord_mut(5, 17) = FieldBlock::Block(Block {
+            color_index: 2,
+            state: BlockState::Stationary,
+        });
+        assert_eq!(table.get(&other_field), None);
+    }
 }
@@ -0,0 +1,166 @@
+//! A variant of the greedy AI that shifts its priorities from scoring to survival once the field
+//! fills up.
+//!
+//! As long as no tower is in danger, moves are rated by [`rate_field`](crate::ai::rate_field).
+//! Once the stack in the spawn column or any other column reaches its threshold, the fields
+//! resulting from each move are settled (all sequences are removed) and rated by
+//! [`SURVIVAL_CRITERIA_NAMES`] instead: keeping the spawn column low comes first, then keeping all
+//! towers low, and only then scoring.
+
+
+use crate::{FIELD_HEIGHT_BLOCKS, FIELD_WIDTH_BLOCKS, NEW_BLOCK_COLUMN};
+use crate::ai::{BestMove, CRITERIA_NAMES, candidate_moves, parallel_map, settle_field};
+use crate::model::Field;
+
+
+/// The default height of the stack in the spawn column at which the AI is in danger.
+pub(crate) const DEFAULT_SPAWN_COLUMN_DANGER_HEIGHT: u32 = 10;
+
+/// The default height of the highest tower at which the AI is in danger.
+pub(crate) const DEFAULT_TOWER_DANGER_HEIGHT: u32 = 13;
+
+
+/// The names of the criteria returned by [`rate_field_for_survival`], in order of importance.
+pub(crate) const SURVIVAL_CRITERIA_NAMES: [&str; 4] = [
+    "negative_spawn_column_height",
+    "negative_tower_height",
+    "score",
+    "extensible_sequences",
+];
+
+
+/// The stack heights at which the AI switches to survival.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct DangerThresholds {
+    /// The height of the stack in the column in which new pieces appear.
+    pub spawn_column_height: u32,
+
+    /// The height of the highest tower in any column.
+    pub tower_height: u32,
+}
+impl Default for DangerThresholds {
+    fn default() -> Self {
+        Self {
+            spawn_column_height: DEFAULT_SPAWN_COLUMN_DANGER_HEIGHT,
+            tower_height: DEFAULT_TOWER_DANGER_HEIGHT,
+        }
+    }
+}
+
+
+/// Returns the height of the stack in the column, not counting the descending blocks.
+fn stack_height(field: &Field, x: u32) -> u32 {
+    let mut height = 0;
+    for y in (0..FIELD_HEIGHT_BLOCKS).rev() {
+        let is_stack = field.block_by_coord(x, y)
+            .as_block()
+            .map(|b| !b.state.is_descending())
+            .unwrap_or(false);
+        if !is_stack {
+            break;
+        }
+        height += 1;
+    }
+    height
+}
+
+
+/// Returns whether the stacks on the field have reached one of the thresholds.
+pub(crate) fn is_in_danger(field: &Field, thresholds: &DangerThresholds) -> bool {
+    if stack_height(field, NEW_BLOCK_COLUMN) >= thresholds.spawn_column_height {
+        return true;
+    }
+    (0..FIELD_WIDTH_BLOCKS)
+        .any(|x| stack_height(field, x) >= thresholds.tower_height)
+}
+
+
+/// Rates the field once all sequences have been removed by the criteria in
+/// [`SURVIVAL_CRITERIA_NAMES`].
+pub(crate) fn rate_field_for_survival(field: &Field) -> Vec<i64> {
+    let mut settled = field.clone();
+    let score = settle_field(&mut settled);
+
+    let spawn_column_height: i64 = stack_height(&settled, NEW_BLOCK_COLUMN).into();
+    let max_tower_height: i64 = (0..FIELD_WIDTH_BLOCKS)
+        .map(|x| stack_height(&settled, x))
+        .max()
+        .unwrap_or(0)
+        .into();
+    let ext_seq_count = settled
+        .get_coordinates_of_sequences(|seq| seq.coordinates.len() > 1)
+        .iter()
+        .filter(|seq| seq.extensible)
+        .count();
+
+    vec![
+        -spawn_column_height,
+        -max_tower_height,
+        score.try_into().unwrap(),
+        ext_seq_count.try_into().unwrap(),
+    ]
+}
+
+
+/// Returns every possible move for the descending blocks along with the resulting field's rating,
+/// in a fixed order, as well as the names of the criteria the ratings consist of.
+pub(crate) fn rate_candidates(
+    base_field: &Field,
+    thresholds: &DangerThresholds,
+    thread_count: usize,
+) -> (Vec<(BestMove, Vec<i64>)>, &'static [&'static str]) {
+    if !is_in_danger(base_field, thresholds) {
+        return (crate::ai::rate_candidates(base_field, thread_count), &CRITERIA_NAMES);
+    }
+
+    let candidates = candidate_moves(base_field);
+    let ratings = parallel_map(&candidates, thread_count, |(field, _best_move)| rate_field_for_survival(field));
+    let rated = candidates.into_iter()
+        .zip(ratings)
+        .map(|((_field, best_move), rating)| (best_move, rating))
+        .collect();
+    (rated, &SURVIVAL_CRITERIA_NAMES)
+}
+
+
+pub(crate) fn pick_best_move(base_field: &Field, thresholds: &DangerThresholds, thread_count: usize) -> Option<BestMove> {
+    let (rated, _criteria_names) = rate_candidates(base_field, thresholds, thread_count);
+    rated.into_iter()
+        .max_by_key(|(_best_move, rating)| rating.clone())
+        .map(|(best_move, _rating)| best_move)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{DangerThresholds, is_in_danger, rate_field_for_survival};
+    use crate::model::{Block, BlockState, Field, FieldBlock};
+
+    fn stack(field: &mut Field, x: u32, colors: &[u8]) {
+        for (i, &color_index) in colors.iter().enumerate() {
+            let y = 17 - u32::try_from(i).unwrap();
+            *field.block_by_coord_mut(x, y) = FieldBlock::Block(Block {
+                color_index,
+                state: BlockState::Stationary,
+            });
+        }
+    }
+
+    #[test]
+    fn test_survival() {
+        let thresholds = DangerThresholds {
+            spawn_column_height: 4,
+            tower_height: 6,
+        };
+
+        let mut field = Field::new();
+        stack(&mut field, 0, &[0, 1, 0, 1, 0]);
+        assert!(!is_in_danger(&field, &thresholds));
+        stack(&mut field, 3, &[2, 3, 2, 3]);
+        assert!(is_in_danger(&field, &thresholds));
+
+        // a vertical sequence of three on top of the spawn column is removed before rating
+        stack(&mut field, 3, &[2, 3, 2, 3, 4, 4, 4]);
+        assert_eq!(rate_field_for_survival(&field), vec![-4, -5, 1, 0]);
+    }
+}
@@ -0,0 +1,181 @@
+use clap::ValueEnum;
+
+use crate::ai::{BestMove, CRITERIA_NAMES, rate_candidates};
+use crate::ai::adaptive::DangerThresholds;
+use crate::model::Field;
+
+
+/// The format in which the AI's reasoning is output.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, ValueEnum)]
+pub(crate) enum AiOutput {
+    /// A human-readable table of the candidates.
+    #[default] Text,
+
+    /// One line of JSON per move.
+    Json,
+}
+
+
+/// Why the chosen move won against the other candidates.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Verdict {
+    /// The move was better than the runner-up in the criterion with the given index and equal in
+    /// all the more important ones.
+    Criterion(usize),
+
+    /// The move was rated equal to the runner-up and won because it came later.
+    Tie,
+
+    /// There was no other candidate.
+    OnlyCandidate,
+
+    /// The move was chosen by a different strategy than the greedy rating.
+    OtherStrategy,
+}
+
+
+/// The ratings of all candidates of a move along with the move that was chosen.
+pub(crate) struct Explanation {
+    pub candidates: Vec<(BestMove, Vec<i64>)>,
+    pub chosen: BestMove,
+
+    /// The names of the criteria the ratings consist of, in order of importance.
+    pub criteria_names: &'static [&'static str],
+}
+impl Explanation {
+    pub fn new(field: &Field, chosen: BestMove, thread_count: usize) -> Self {
+        Self {
+            candidates: rate_candidates(field, thread_count),
+            chosen,
+            criteria_names: &CRITERIA_NAMES,
+        }
+    }
+
+    /// Explains a move of the adaptive strategy, which rates the candidates by different criteria
+    /// when it is in danger.
+    pub fn new_adaptive(field: &Field, chosen: BestMove, thresholds: &DangerThresholds, thread_count: usize) -> Self {
+        let (candidates, criteria_names) = crate::ai::adaptive::rate_candidates(field, thresholds, thread_count);
+        Self {
+            candidates,
+            chosen,
+            criteria_names,
+        }
+    }
+
+    fn rating_of(&self, best_move: &BestMove) -> Option<&[i64]> {
+        self.candidates.iter()
+            .find(|(bm, _rating)| bm == best_move)
+            .map(|(_bm, rating)| rating.as_slice())
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        let chosen_rating = match self.rating_of(&self.chosen) {
+            Some(r) => r,
+            None => return Verdict::OtherStrategy,
+        };
+        let runner_up_rating = self.candidates.iter()
+            .filter(|(bm, _rating)| *bm != self.chosen)
+            .map(|(_bm, rating)| rating.as_slice())
+            .max();
+        let runner_up_rating = match runner_up_rating {
+            Some(r) => r,
+            None => return Verdict::OnlyCandidate,
+        };
+
+        if runner_up_rating > chosen_rating {
+            return Verdict::OtherStrategy;
+        }
+        chosen_rating.iter()
+            .zip(runner_up_rating)
+            .position(|(chosen, runner_up)| chosen != runner_up)
+            .map(Verdict::Criterion)
+            .unwrap_or(Verdict::Tie)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut ret = String::new();
+        ret.push_str("AI candidates:\n");
+        ret.push_str("  column rotation");
+        for name in self.criteria_names {
+            ret.push_str(&format!(" {:>width$}", name, width = name.len()));
+        }
+        ret.push('\n');
+        for (best_move, rating) in &self.candidates {
+            let marker = if *best_move == self.chosen { '*' } else { ' ' };
+            ret.push_str(&format!("{} {:>6} {:>8}", marker, best_move.column, best_move.rotate_count));
+            for (name, value) in self.criteria_names.iter().zip(rating) {
+                ret.push_str(&format!(" {:>width$}", value, width = name.len()));
+            }
+            ret.push('\n');
+        }
+
+        ret.push_str(&format!(
+            "AI says best move is column {} rotation {}",
+            self.chosen.column, self.chosen.rotate_count,
+        ));
+        match self.verdict() {
+            Verdict::Criterion(index) => ret.push_str(&format!(", decided by {}", self.criteria_names[index])),
+            Verdict::Tie => ret.push_str(", tied with the runner-up"),
+            Verdict::OnlyCandidate => ret.push_str(", the only possible move"),
+            Verdict::OtherStrategy => ret.push_str(", chosen by a different strategy"),
+        }
+        ret.push('\n');
+        ret
+    }
+
+    pub fn to_json(&self) -> String {
+        let candidate_strings: Vec<String> = self.candidates.iter()
+            .map(|(best_move, rating)| {
+                let mut s = format!("{{\"column\":{},\"rotation\":{}", best_move.column, best_move.rotate_count);
+                for (name, value) in self.criteria_names.iter().zip(rating) {
+                    s.push_str(&format!(",\"{}\":{}", name, value));
+                }
+                s.push('}');
+                s
+            })
+            .collect();
+        let decided_by = match self.verdict() {
+            Verdict::Criterion(index) => self.criteria_names[index],
+            Verdict::Tie => "tie",
+            Verdict::OnlyCandidate => "only_candidate",
+            Verdict::OtherStrategy => "other_strategy",
+        };
+        format!(
+            "{{\"best\":{{\"column\":{},\"rotation\":{}}},\"decided_by\":\"{}\",\"candidates\":[{}]}}",
+            self.chosen.column, self.chosen.rotate_count, decided_by, candidate_strings.join(","),
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Explanation, Verdict};
+    use crate::ai::{BestMove, CRITERIA_NAMES};
+
+    fn explanation(chosen_column: u32, ratings: &[Vec<i64>]) -> Explanation {
+        Explanation {
+            candidates: ratings.iter()
+                .enumerate()
+                .map(|(i, rating)| (BestMove { column: i.try_into().unwrap(), rotate_count: 0 }, rating.clone()))
+                .collect(),
+            chosen: BestMove { column: chosen_column, rotate_count: 0 },
+            criteria_names: &CRITERIA_NAMES,
+        }
+    }
+
+    #[test]
+    fn test_verdict() {
+        assert_eq!(explanation(1, &[vec![0, 3, -5], vec![0, 4, -6]]).verdict(), Verdict::Criterion(1));
+        assert_eq!(explanation(0, &[vec![1, 0, -9], vec![0, 4, -6]]).verdict(), Verdict::Criterion(0));
+        assert_eq!(explanation(1, &[vec![0, 4, -6], vec![0, 4, -6]]).verdict(), Verdict::Tie);
+        assert_eq!(explanation(0, &[vec![0, 4, -6]]).verdict(), Verdict::OnlyCandidate);
+        assert_eq!(explanation(0, &[vec![0, 3, -5], vec![0, 4, -6]]).verdict(), Verdict::OtherStrategy);
+
+        let json = explanation(1, &[vec![0, 3, -5], vec![0, 4, -6]]).to_json();
+        assert_eq!(
+            json,
+            "{\"best\":{\"column\":1,\"rotation\":0},\"decided_by\":\"extensible_sequences\",\"candidates\":[{\"column\":0,\"rotation\":0,\"score\":0,\"extensible_sequences\":3,\"negative_tower_height\":-5},{\"column\":1,\"rotation\":0,\"score\":0,\"extensible_sequences\":4,\"negative_tower_height\":-6}]}",
+        );
+    }
+}
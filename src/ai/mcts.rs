@@ -0,0 +1,169 @@
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand::distributions::Uniform;
+use rand::rngs::StdRng;
+
+use crate::BLOCK_COLOR_COUNT;
+use crate::ai::{BestMove, TranspositionTable, candidate_moves, parallel_map, settle_field};
+use crate::model::Field;
+
+
+/// How many pieces are placed during each rollout.
+const ROLLOUT_DEPTH: usize = 8;
+
+/// The probability with which a rollout places a piece at random instead of using the greedy
+/// rating.
+const ROLLOUT_RANDOM_MOVE_PROBABILITY: f64 = 0.25;
+
+/// The value of a rollout that ends with the field overflowing.
+const GAME_OVER_PENALTY: f64 = -100.0;
+
+/// The exploration constant of the UCB1 formula.
+const EXPLORATION: f64 = 1.4;
+
+
+/// The rollout statistics of one possible move.
+#[derive(Clone, Copy, Debug, Default)]
+struct CandidateStats {
+    total_value: f64,
+    rollout_count: u64,
+}
+impl CandidateStats {
+    fn average_value(&self) -> f64 {
+        if self.rollout_count == 0 {
+            f64::NEG_INFINITY
+        } else {
+            self.total_value / (self.rollout_count as f64)
+        }
+    }
+
+    fn upper_confidence_bound(&self, total_rollouts: u64) -> f64 {
+        if self.rollout_count == 0 {
+            return f64::INFINITY;
+        }
+        let exploration = EXPLORATION
+            * ((total_rollouts as f64).ln() / (self.rollout_count as f64)).sqrt();
+        self.average_value() + exploration
+    }
+}
+
+
+/// Plays out random future pieces starting from the given field (whose descending blocks have
+/// already been dropped) and returns the score gained along the way.
+fn rollout(start_field: &Field, rng: &mut StdRng, table: &mut TranspositionTable) -> f64 {
+    let color_distribution = Uniform::new(0, u8::try_from(BLOCK_COLOR_COUNT).unwrap());
+    let mut color_stats = [0u32; BLOCK_COLOR_COUNT];
+
+    let mut field = start_field.clone();
+    let mut value = settle_field(&mut field) as f64;
+
+    for _ in 0..ROLLOUT_DEPTH {
+        if !field.make_new_descending_block(&color_distribution, rng, &mut color_stats) {
+            return value + GAME_OVER_PENALTY;
+        }
+
+        let mut candidates = candidate_moves(&field);
+        if candidates.is_empty() {
+            return value + GAME_OVER_PENALTY;
+        }
+
+        let next_field = if rng.gen_bool(ROLLOUT_RANDOM_MOVE_PROBABILITY) {
+            let index = rng.gen_range(0..candidates.len());
+            candidates.swap_remove(index).0
+        } else {
+            candidates.into_iter()
+                .max_by_key(|(candidate_field, _best_move)| table.rate_field(candidate_field))
+                .map(|(candidate_field, _best_move)| candidate_field)
+                .unwrap()
+        };
+
+        field = next_field;
+        value += settle_field(&mut field) as f64;
+    }
+
+    value
+}
+
+
+/// Performs rollouts on the candidate fields until the deadline has passed and returns the
+/// statistics for each candidate.
+///
+/// Each candidate is played out at least once, even if this exceeds the deadline.
+fn run_rollouts(candidate_fields: &[Field], deadline: Instant, rng: &mut StdRng) -> Vec<CandidateStats> {
+    let mut stats = vec![CandidateStats::default(); candidate_fields.len()];
+    let mut table = TranspositionTable::new();
+
+    let mut total_rollouts = 0;
+    loop {
+        // pick the candidate with the highest upper confidence bound
+        // (the first one wins ties to remain deterministic)
+        let mut best_index = 0;
+        let mut best_bound = f64::NEG_INFINITY;
+        for (i, candidate_stats) in stats.iter().enumerate() {
+            let bound = candidate_stats.upper_confidence_bound(total_rollouts);
+            if bound > best_bound {
+                best_index = i;
+                best_bound = bound;
+            }
+        }
+
+        stats[best_index].total_value += rollout(&candidate_fields[best_index], rng, &mut table);
+        stats[best_index].rollout_count += 1;
+        total_rollouts += 1;
+
+        if total_rollouts >= u64::try_from(candidate_fields.len()).unwrap() && Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    stats
+}
+
+
+/// Picks the move whose rollouts have the best average outcome within the given time budget.
+///
+/// Each thread performs its own rollouts with a generator seeded from `rng`; their statistics are
+/// combined once the time budget is spent.
+pub(crate) fn pick_best_move(
+    base_field: &Field,
+    time_budget: Duration,
+    thread_count: usize,
+    rng: &mut StdRng,
+) -> Option<BestMove> {
+    let deadline = Instant::now() + time_budget;
+
+    let (candidate_fields, moves): (Vec<Field>, Vec<BestMove>) = candidate_moves(base_field)
+        .into_iter()
+        .unzip();
+    if candidate_fields.is_empty() {
+        return None;
+    }
+
+    // seed the generators up front so that they do not depend on thread scheduling
+    let thread_seeds: Vec<u64> = (0..thread_count.max(1))
+        .map(|_| rng.gen())
+        .collect();
+    let thread_stats = parallel_map(&thread_seeds, thread_count, |&seed| {
+        let mut thread_rng = StdRng::seed_from_u64(seed);
+        run_rollouts(&candidate_fields, deadline, &mut thread_rng)
+    });
+
+    let mut stats = vec![CandidateStats::default(); candidate_fields.len()];
+    for single_thread_stats in &thread_stats {
+        for (total, single) in stats.iter_mut().zip(single_thread_stats) {
+            total.total_value += single.total_value;
+            total.rollout_count += single.rollout_count;
+        }
+    }
+
+    // pick the candidate with the best average outcome
+    // (the first one wins ties to remain deterministic)
+    let mut best_index = 0;
+    for (i, candidate_stats) in stats.iter().enumerate() {
+        if candidate_stats.average_value() > stats[best_index].average_value() {
+            best_index = i;
+        }
+    }
+    Some(moves[best_index])
+}
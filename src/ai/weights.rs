@@ -0,0 +1,95 @@
+//! An alternative to the lexicographic ordering of the criteria of [`rate_field`]: a weighted sum.
+//!
+//! Weight files are plain text. Empty lines and lines starting with `#` are ignored; every other
+//! line consists of the name of a criterion (see [`CRITERIA_NAMES`]) and its weight:
+//!
+//! ```text
+//! score 10
+//! extensible_sequences 1.5
+//! negative_tower_height 0.5
+//! ```
+//!
+//! Criteria that are not mentioned have a weight of 0.
+//!
+//! [`rate_field`]: crate::ai::rate_field
+
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::ai::{BestMove, CRITERIA_NAMES, rate_candidates};
+use crate::model::Field;
+
+
+/// The weight of each criterion of [`rate_field`](crate::ai::rate_field).
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub(crate) struct Weights {
+    pub criteria: [f64; CRITERIA_NAMES.len()],
+}
+impl Weights {
+    /// Parses weights in the format described at the top of this module.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut criteria = [0.0; CRITERIA_NAMES.len()];
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, value) = line.split_once(char::is_whitespace)
+                .ok_or_else(|| format!("invalid line {:?}", line))?;
+            let index = CRITERIA_NAMES.iter()
+                .position(|criterion_name| *criterion_name == name)
+                .ok_or_else(|| format!("unknown criterion {:?}", name))?;
+            criteria[index] = value.trim().parse()
+                .map_err(|_| format!("invalid weight {:?}", value.trim()))?;
+        }
+        Ok(Self {
+            criteria,
+        })
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Returns the weighted sum of the rating.
+    pub fn apply(&self, rating: &[i64]) -> f64 {
+        self.criteria.iter()
+            .zip(rating)
+            .map(|(weight, &value)| weight * (value as f64))
+            .sum()
+    }
+}
+
+
+/// Picks the move whose resulting field has the highest weighted sum of its rating.
+pub(crate) fn pick_best_move(base_field: &Field, weights: &Weights, thread_count: usize) -> Option<BestMove> {
+    // as with the lexicographic ordering, the last of equally-rated candidates wins
+    rate_candidates(base_field, thread_count)
+        .into_iter()
+        .map(|(best_move, rating)| (best_move, weights.apply(&rating)))
+        .fold(None, |best: Option<(BestMove, f64)>, (best_move, value)| match best {
+            Some((_, best_value)) if best_value > value => best,
+            _ => Some((best_move, value)),
+        })
+        .map(|(best_move, _value)| best_move)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Weights;
+
+    #[test]
+    fn test_parse_weights() {
+        let weights = Weights::parse("# comment\nscore 10\n\nnegative_tower_height 0.5\n").unwrap();
+        assert_eq!(weights.criteria, [10.0, 0.0, 0.5]);
+        assert_eq!(weights.apply(&[2, 7, -4]), 18.0);
+        assert!(Weights::parse("height 1").is_err());
+        assert!(Weights::parse("score lots").is_err());
+    }
+}
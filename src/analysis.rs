@@ -0,0 +1,262 @@
+//! Post-game analysis of recorded games.
+//!
+//! Each placement in a record file is compared with every alternative the greedy AI considers. A
+//! placement is flagged as a mistake if, at the first criterion of [`rate_field`] in which it
+//! differs from the best alternative, it falls behind by at least the threshold configured for
+//! that criterion.
+//!
+//! [`rate_field`]: crate::ai::rate_field
+
+
+use std::io;
+use std::path::Path;
+
+use crate::ai::{BestMove, CRITERIA_NAMES, candidate_moves, rate_field, settle_field};
+use crate::model::Field;
+use crate::record::{Decision, read_decisions};
+
+
+/// How far a placement may fall behind the best alternative in each criterion of
+/// [`rate_field`](crate::ai::rate_field) before it is flagged.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Thresholds {
+    pub criteria: [i64; CRITERIA_NAMES.len()],
+}
+impl Thresholds {
+    /// Returns the criterion in which the placement is a mistake along with the amount it falls
+    /// behind by, or `None` if it isn't one.
+    fn mistake(&self, analysis: &MoveAnalysis) -> Option<(usize, i64)> {
+        let (criterion_index, deficit) = analysis.first_deficit()?;
+        if deficit < self.criteria[criterion_index] {
+            None
+        } else {
+            Some((criterion_index, deficit))
+        }
+    }
+}
+
+
+/// The analysis of a single placement.
+struct MoveAnalysis {
+    chosen_move: BestMove,
+    chosen_field: Field,
+    chosen_rating: Vec<i64>,
+    best_move: BestMove,
+    best_field: Field,
+    best_rating: Vec<i64>,
+}
+impl MoveAnalysis {
+    /// Returns the index of the first criterion in which the chosen move falls behind the best
+    /// move, along with the amount.
+    fn first_deficit(&self) -> Option<(usize, i64)> {
+        self.chosen_rating.iter()
+            .zip(&self.best_rating)
+            .enumerate()
+            .find(|(_i, (chosen, best))| chosen != best)
+            .map(|(i, (chosen, best))| (i, best - chosen))
+    }
+}
+
+
+/// Compares the placement of a recorded decision with all alternatives.
+///
+/// Returns `None` if the recorded placement is not possible on the recorded field.
+fn analyze_decision(decision: &Decision) -> Option<MoveAnalysis> {
+    let field = decision.field_with_piece();
+    let piece_length = decision.piece.len().max(1);
+    let chosen_move = BestMove {
+        column: decision.best_move.column,
+        rotate_count: decision.best_move.rotate_count % piece_length,
+    };
+
+    let rated_candidates: Vec<(Field, BestMove, Vec<i64>)> = candidate_moves(&field)
+        .into_iter()
+        .map(|(candidate_field, best_move)| {
+            let rating = rate_field(&candidate_field);
+            (candidate_field, best_move, rating)
+        })
+        .collect();
+
+    let (chosen_field, _, chosen_rating) = rated_candidates.iter()
+        .find(|(_field, best_move, _rating)| *best_move == chosen_move)?
+        .clone();
+    let (best_field, best_move, best_rating) = rated_candidates.into_iter()
+        .max_by_key(|(_field, _best_move, rating)| rating.clone())?;
+
+    Some(MoveAnalysis {
+        chosen_move,
+        chosen_field,
+        chosen_rating,
+        best_move,
+        best_field,
+        best_rating,
+    })
+}
+
+
+/// Places the diagrams of multiple fields next to each other, each under its own title.
+pub(crate) fn side_by_side(titled_fields: &[(&str, &Field)]) -> String {
+    let diagrams: Vec<Vec<String>> = titled_fields.iter()
+        .map(|(_title, field)| field.to_string().lines().map(|l| l.to_owned()).collect())
+        .collect();
+    let width = diagrams.iter()
+        .flat_map(|lines| lines.iter().map(|l| l.chars().count()))
+        .chain(titled_fields.iter().map(|(title, _field)| title.chars().count()))
+        .max()
+        .unwrap_or(0);
+
+    let mut ret = String::new();
+    let titles: Vec<String> = titled_fields.iter()
+        .map(|(title, _field)| format!("{:width$}", title, width = width))
+        .collect();
+    ret.push_str(titles.join("  ").trim_end());
+    ret.push('\n');
+
+    let line_count = diagrams.iter().map(|lines| lines.len()).max().unwrap_or(0);
+    for i in 0..line_count {
+        let line_pieces: Vec<String> = diagrams.iter()
+            .map(|lines| format!("{:width$}", lines.get(i).map(|l| l.as_str()).unwrap_or(""), width = width))
+            .collect();
+        ret.push_str(line_pieces.join("  ").trim_end());
+        ret.push('\n');
+    }
+    ret
+}
+
+
+fn format_rating(rating: &[i64]) -> String {
+    let pieces: Vec<String> = CRITERIA_NAMES.iter()
+        .zip(rating)
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    pieces.join(", ")
+}
+
+
+/// Analyzes all the games in the record file and prints a report of the mistakes on standard
+/// output.
+pub(crate) fn analyze(path: &Path, thresholds: &Thresholds) -> io::Result<()> {
+    let decisions = read_decisions(path)?;
+
+    let mut current_game = None;
+    let mut game_moves = 0;
+    let mut game_mistakes = 0;
+    let mut game_points_lost = 0;
+    for decision in &decisions {
+        if current_game != Some(decision.game) {
+            if let Some(game) = current_game {
+                println!(
+                    "game {}: {} mistakes in {} moves, {} points left on the table",
+                    game, game_mistakes, game_moves, game_points_lost,
+                );
+                println!();
+            }
+            current_game = Some(decision.game);
+            game_moves = 0;
+            game_mistakes = 0;
+            game_points_lost = 0;
+        }
+        game_moves += 1;
+
+        let analysis = match analyze_decision(decision) {
+            Some(a) => a,
+            None => {
+                println!("game {} move {}: recorded placement is not possible; skipping", decision.game, decision.move_index);
+                continue;
+            },
+        };
+        let (criterion_index, deficit) = match thresholds.mistake(&analysis) {
+            Some(m) => m,
+            None => continue,
+        };
+
+        game_mistakes += 1;
+        game_points_lost += analysis.best_rating[0] - analysis.chosen_rating[0];
+
+        println!(
+            "game {} move {} ({}): {} behind by {}",
+            decision.game, decision.move_index, decision.player.as_str(),
+            CRITERIA_NAMES[criterion_index], deficit,
+        );
+        println!(
+            "  played: column {} rotation {} ({})",
+            analysis.chosen_move.column, analysis.chosen_move.rotate_count,
+            format_rating(&analysis.chosen_rating),
+        );
+        println!(
+            "  best:   column {} rotation {} ({})",
+            analysis.best_move.column, analysis.best_move.rotate_count,
+            format_rating(&analysis.best_rating),
+        );
+
+        let mut chosen_settled = analysis.chosen_field.clone();
+        settle_field(&mut chosen_settled);
+        let mut best_settled = analysis.best_field.clone();
+        settle_field(&mut best_settled);
+        print!("{}", side_by_side(&[
+            ("before", &decision.field_with_piece()),
+            ("played", &chosen_settled),
+            ("best", &best_settled),
+        ]));
+        println!();
+    }
+
+    if let Some(game) = current_game {
+        println!(
+            "game {}: {} mistakes in {} moves, {} points left on the table",
+            game, game_mistakes, game_moves, game_points_lost,
+        );
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{MoveAnalysis, Thresholds, side_by_side};
+    use crate::ai::BestMove;
+    use crate::model::{Block, BlockState, Field, FieldBlock};
+
+    fn analysis(chosen_rating: &[i64], best_rating: &[i64]) -> MoveAnalysis {
+        MoveAnalysis {
+            chosen_move: BestMove { column: 0, rotate_count: 0 },
+            chosen_field: Field::new(),
+            chosen_rating: chosen_rating.to_vec(),
+            best_move: BestMove { column: 1, rotate_count: 0 },
+            best_field: Field::new(),
+            best_rating: best_rating.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_mistakes() {
+        assert_eq!(analysis(&[2, 5, -3], &[2, 5, -3]).first_deficit(), None);
+        // only the first criterion that differs counts, even if a later one differs more
+        assert_eq!(analysis(&[2, 3, -9], &[2, 5, -3]).first_deficit(), Some((1, 2)));
+        assert_eq!(analysis(&[0, 9, 0], &[1, 5, -3]).first_deficit(), Some((0, 1)));
+
+        let thresholds = Thresholds { criteria: [1, 3, 2] };
+        assert_eq!(thresholds.mistake(&analysis(&[2, 5, -3], &[2, 5, -3])), None);
+        assert_eq!(thresholds.mistake(&analysis(&[0, 9, 0], &[1, 5, -3])), Some((0, 1)));
+        assert_eq!(thresholds.mistake(&analysis(&[2, 3, -9], &[2, 5, -3])), None);
+        assert_eq!(thresholds.mistake(&analysis(&[2, 2, -9], &[2, 5, -3])), Some((1, 3)));
+        assert_eq!(thresholds.mistake(&analysis(&[2, 5, -4], &[2, 5, -3])), None);
+        assert_eq!(thresholds.mistake(&analysis(&[2, 5, -5], &[2, 5, -3])), Some((2, 2)));
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let mut field = Field::new();
+        *field.block_by_coord_mut(2, 17) = FieldBlock::Block(Block {
+            color_index: 4,
+            state: BlockState::Stationary,
+        });
+        let text = side_by_side(&[("a long title", &Field::new()), ("b", &field)]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1 + 18 + 2);
+        assert_eq!(lines[0], "a long title  b");
+        assert_eq!(lines[1], "\u{250C}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2510}      \u{250C}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2510}");
+        assert_eq!(lines[19], "\u{2502}      \u{2502}      \u{2502}  4   \u{2502}");
+    }
+}
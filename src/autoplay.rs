@@ -0,0 +1,88 @@
+use crate::ai::BestMove;
+use crate::game::{Game, GameState, Piece};
+
+
+/// Carries out a move of the AI one action (a move to the side or a rotation) at a time, the way a
+/// human player would.
+pub(crate) struct AnimatedMove {
+    target_column: u32,
+    rotations_left: usize,
+    frames_per_action: u32,
+    frames_until_action: u32,
+    drop_when_done: bool,
+}
+impl AnimatedMove {
+    /// Prepares the given move.
+    ///
+    /// The first action is performed once `delay_frames` frames have passed and every further
+    /// action `frames_per_action` frames after the previous one; if `frames_per_action` is 0, all
+    /// actions are performed at once. If `drop_when_done` is set, the piece is dropped once it has
+    /// reached its target.
+    pub fn new(best_move: &BestMove, frames_per_action: u32, delay_frames: u32, drop_when_done: bool) -> Self {
+        Self {
+            target_column: best_move.column,
+            rotations_left: best_move.rotate_count,
+            frames_per_action,
+            frames_until_action: delay_frames,
+            drop_when_done,
+        }
+    }
+
+    /// Performs the next action if it is due. Returns whether the move is complete.
+    pub fn tick(&mut self, game: &mut Game) -> bool {
+        if !game.state.is_running() {
+            // wait until the game continues
+            return game.state == GameState::Over;
+        }
+        if Piece::from_field(&game.field).is_none() {
+            // the piece has landed or been dropped in the meantime
+            return true;
+        }
+
+        if self.frames_until_action > 0 {
+            self.frames_until_action -= 1;
+            return false;
+        }
+
+        loop {
+            if !self.perform_action(game) {
+                if self.drop_when_done {
+                    game.drop_piece();
+                }
+                return true;
+            }
+            if self.frames_per_action > 0 {
+                // the first frame is the one we are in
+                self.frames_until_action = self.frames_per_action - 1;
+                return false;
+            }
+        }
+    }
+
+    /// Performs a single action. Returns `false` if there was nothing left to do.
+    fn perform_action(&mut self, game: &mut Game) -> bool {
+        // rotate first, while the piece is still far away from the stack
+        if self.rotations_left > 0 {
+            game.field.rotate_descending_blocks();
+            self.rotations_left -= 1;
+            return true;
+        }
+
+        let current_column = match Piece::from_field(&game.field) {
+            Some(p) => p.column,
+            None => return false,
+        };
+        if current_column < self.target_column {
+            game.field.move_descending_blocks_right();
+        } else if current_column > self.target_column {
+            game.field.move_descending_blocks_left();
+        } else {
+            return false;
+        }
+
+        // give up if the piece is blocked
+        Piece::from_field(&game.field)
+            .map(|p| p.column != current_column)
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,251 @@
+//! Communication with external bot processes.
+//!
+//! The game talks to the bot using a line-based text protocol on the bot's standard input and
+//! output. Once the bot has been started, the game sends:
+//!
+//! ```text
+//! columns 1
+//! ```
+//!
+//! where `1` is the protocol version. The bot answers with `ok`. Whenever a new piece appears, the
+//! game sends:
+//!
+//! ```text
+//! field 6 18
+//! ......
+//! (one line per row from top to bottom, 18 in total)
+//! ...2..
+//! piece 3 0 4 5
+//! queue
+//! go
+//! ```
+//!
+//! `field` is followed by the width and height of the field and then one line per row; `.` is an
+//! empty cell and a digit is the color index of a block. The descending piece is not part of the
+//! field. `piece` is followed by the column in which the piece appears and the color indexes of its
+//! blocks from top to bottom. `queue` lists the upcoming pieces as groups of color indexes from top
+//! to bottom (e.g. `queue 012 345`); since the game does not preview pieces, it is currently always
+//! empty.
+//!
+//! The bot answers with:
+//!
+//! ```text
+//! move 2 1
+//! ```
+//!
+//! where `2` is the column into which the piece should be moved and `1` is how often the piece
+//! should be rotated, which must be less than the length of the piece. Each rotation moves every
+//! color one block down and the bottommost color to the top. When the game ends, it sends `quit` and closes the bot's standard input.
+//!
+//! The bot has ten seconds to answer each request; a bot that takes longer is stopped and asked for
+//! no further moves.
+
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::{FIELD_WIDTH_BLOCKS, PIECE_LENGTH};
+use crate::ai::BestMove;
+use crate::env::Observation;
+use crate::model::Field;
+
+
+/// The version of the protocol spoken with the bot.
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+
+/// How long the bot may take to answer a request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+
+fn protocol_error<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+
+/// Parses a `move` line sent by the bot.
+pub(crate) fn parse_move(line: &str) -> io::Result<BestMove> {
+    let mut pieces = line.split_whitespace();
+    if pieces.next() != Some("move") {
+        return Err(protocol_error(format!("expected \"move\", got {:?}", line)));
+    }
+    let column: u32 = pieces.next()
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| protocol_error(format!("invalid column in {:?}", line)))?;
+    let rotate_count: usize = pieces.next()
+        .and_then(|r| r.parse().ok())
+        .ok_or_else(|| protocol_error(format!("invalid rotation in {:?}", line)))?;
+    if pieces.next().is_some() {
+        return Err(protocol_error(format!("trailing data in {:?}", line)));
+    }
+    if column >= FIELD_WIDTH_BLOCKS {
+        return Err(protocol_error(format!("column {} out of range", column)));
+    }
+    if rotate_count >= PIECE_LENGTH {
+        // each rotation is carried out one by one; don't spend forever on huge numbers
+        return Err(protocol_error(format!("rotation {} out of range", rotate_count)));
+    }
+    Ok(BestMove {
+        column,
+        rotate_count,
+    })
+}
+
+
+/// An external bot process that picks moves.
+pub(crate) struct Bot {
+    child: Child,
+    stdin: Option<ChildStdin>,
+
+    /// The lines written by the bot, which are read on a separate thread so that waiting for them
+    /// can time out.
+    lines: Receiver<io::Result<String>>,
+
+    reply_timeout: Duration,
+}
+impl Bot {
+    /// Starts the bot using the given command line and performs the handshake.
+    ///
+    /// The command line is split at whitespace; the first piece is the program and the others are
+    /// its arguments.
+    pub fn spawn(command_line: &str) -> io::Result<Self> {
+        Self::spawn_with_timeout(command_line, REPLY_TIMEOUT)
+    }
+
+    fn spawn_with_timeout(command_line: &str, reply_timeout: Duration) -> io::Result<Self> {
+        let mut pieces = command_line.split_whitespace();
+        let program = pieces.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(pieces)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (line_sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if line_sender.send(line).is_err() {
+                    // the bot has been dropped
+                    break;
+                }
+            }
+        });
+
+        let mut bot = Self {
+            child,
+            stdin: Some(stdin),
+            lines,
+            reply_timeout,
+        };
+        bot.send(&format!("columns {}\n", PROTOCOL_VERSION))?;
+        let response = bot.receive()?;
+        if response != "ok" {
+            return Err(protocol_error(format!("expected \"ok\", got {:?}", response)));
+        }
+        Ok(bot)
+    }
+
+    fn send(&mut self, text: &str) -> io::Result<()> {
+        let stdin = self.stdin.as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "bot has quit"))?;
+        stdin.write_all(text.as_bytes())?;
+        stdin.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<String> {
+        match self.lines.recv_timeout(self.reply_timeout) {
+            Ok(line) => Ok(line?.trim().to_owned()),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "bot closed its output")),
+            Err(RecvTimeoutError::Timeout) => {
+                // a late answer would be mistaken for the next one; stop the bot instead
+                self.stdin = None;
+                let _ = self.child.kill();
+                Err(io::Error::new(io::ErrorKind::TimedOut, "bot did not answer in time"))
+            },
+        }
+    }
+
+    /// Asks the bot for the best move for the descending blocks on the field.
+    pub fn pick_best_move(&mut self, field: &Field) -> io::Result<BestMove> {
+        let mut request = Vec::new();
+        Observation::from_field(field).write(&mut request)?;
+        writeln!(request, "go")?;
+        self.send(std::str::from_utf8(&request).unwrap())?;
+
+        let response = self.receive()?;
+        parse_move(&response)
+    }
+}
+impl Drop for Bot {
+    fn drop(&mut self) {
+        // say goodbye, close the pipe and wait for the bot to exit
+        let _ = self.send("quit\n");
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::{Bot, parse_move};
+    use crate::NEW_BLOCK_COLUMN;
+    use crate::env::Observation;
+    use crate::model::{Block, BlockState, Field, FieldBlock};
+
+    #[test]
+    fn test_parse_move() {
+        let best_move = parse_move("move 2 1").unwrap();
+        assert_eq!(best_move.column, 2);
+        assert_eq!(best_move.rotate_count, 1);
+
+        assert!(parse_move("move 6 0").is_err());
+        assert!(parse_move("move 2 3").is_err());
+        assert!(parse_move("move 2 18446744073709551615").is_err());
+        assert!(parse_move("move 2").is_err());
+        assert!(parse_move("move 2 1 0").is_err());
+        assert!(parse_move("go 2 1").is_err());
+    }
+
+    #[test]
+    fn test_write_observation() {
+        let mut field = Field::new();
+        *field.block_by_coord_mut(0, 17) = FieldBlock::Block(Block {
+            color_index: 4,
+            state: BlockState::Stationary,
+        });
+        for (y, color_index) in [5, 1, 3].into_iter().enumerate() {
+            *field.block_by_coord_mut(NEW_BLOCK_COLUMN, y.try_into().unwrap()) = FieldBlock::Block(Block {
+                color_index,
+                state: BlockState::Descending,
+            });
+        }
+
+        let mut output = Vec::new();
+        Observation::from_field(&field).write(&mut output).unwrap();
+        let output_str = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output_str.lines().collect();
+
+        assert_eq!(lines.len(), 21);
+        assert_eq!(lines[0], "field 6 18");
+        assert_eq!(lines[1], "......");
+        assert_eq!(lines[18], "4.....");
+        assert_eq!(lines[19], "piece 3 5 1 3");
+        assert_eq!(lines[20], "queue");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout() {
+        let error = Bot::spawn_with_timeout("sleep 10", Duration::from_millis(100))
+            .err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
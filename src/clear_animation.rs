@@ -0,0 +1,137 @@
+//! The animations with which the blocks of completed sequences disappear.
+
+
+use clap::ValueEnum;
+
+use crate::DISAPPEAR_BLINK_COUNT;
+use crate::render::Rgba;
+use crate::render::software::Image;
+
+
+/// The number of images that the fading and dissolving animations consist of.
+const STAGE_COUNT: usize = 16;
+
+/// The number of pieces per row and column into which blocks dissolve.
+const DISSOLVE_PIECES: u32 = 10;
+
+
+/// How the blocks of completed sequences disappear.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, ValueEnum)]
+pub(crate) enum ClearAnimation {
+    /// The blocks alternate between their color and white.
+    #[default] Blink,
+
+    /// The blocks become more and more transparent.
+    Fade,
+
+    /// The blocks shrink towards their middle.
+    Shrink,
+
+    /// The blocks crumble into pieces which disappear one by one.
+    Dissolve,
+
+    /// The blocks are shown brightened until they disappear.
+    Highlight,
+}
+impl ClearAnimation {
+    /// Returns whether the animation flashes, which some players are sensitive to.
+    pub fn flashes(self) -> bool {
+        self == Self::Blink
+    }
+
+    /// Returns the images that the animation shows in turn for the block image, if it consists of
+    /// images.
+    pub fn stage_images(self, image: &Image) -> Vec<Image> {
+        match self {
+            Self::Blink|Self::Shrink => Vec::new(),
+            Self::Fade => (0..STAGE_COUNT)
+                .map(|stage| {
+                    let mut faded = image.clone();
+                    for pixel in &mut faded.pixels {
+                        let alpha = usize::from(pixel.a) * (STAGE_COUNT - stage) / STAGE_COUNT;
+                        pixel.a = alpha.try_into().unwrap();
+                    }
+                    faded
+                })
+                .collect(),
+            Self::Dissolve => (0..STAGE_COUNT)
+                .map(|stage| {
+                    let mut dissolved = image.clone();
+                    for y in 0..image.height {
+                        for x in 0..image.width {
+                            let piece_x = x * DISSOLVE_PIECES / image.width;
+                            let piece_y = y * DISSOLVE_PIECES / image.height;
+                            if piece_stage(piece_x, piece_y) < stage {
+                                let index = usize::try_from(y * image.width + x).unwrap();
+                                dissolved.pixels[index] = Rgba::BLACK.with_alpha(0);
+                            }
+                        }
+                    }
+                    dissolved
+                })
+                .collect(),
+            Self::Highlight => {
+                let mut highlighted = image.clone();
+                for pixel in &mut highlighted.pixels {
+                    let brighten = |value: u8| value + (0xFF - value) / 2;
+                    *pixel = Rgba::new(brighten(pixel.r), brighten(pixel.g), brighten(pixel.b), pixel.a);
+                }
+                vec![highlighted]
+            },
+        }
+    }
+}
+
+
+/// Returns the stage of a dissolving block from which on the piece is gone, scattered
+/// pseudo-randomly but the same way for every block.
+fn piece_stage(piece_x: u32, piece_y: u32) -> usize {
+    let mut hash = piece_x.wrapping_mul(0x9E37_79B9) ^ piece_y.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    usize::try_from(hash).unwrap() % STAGE_COUNT
+}
+
+
+/// Returns which of the images of an animation to show for a disappearing block with the given
+/// counter.
+pub(crate) fn stage_index(counter: usize, stage_count: usize) -> usize {
+    let elapsed = DISAPPEAR_BLINK_COUNT - counter.min(DISAPPEAR_BLINK_COUNT);
+    elapsed * stage_count / (DISAPPEAR_BLINK_COUNT + 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{ClearAnimation, STAGE_COUNT, stage_index};
+    use crate::DISAPPEAR_BLINK_COUNT;
+    use crate::render::Rgba;
+    use crate::render::software::Image;
+
+    #[test]
+    fn test_stages() {
+        let image = Image::new(20, 20, Rgba::rgb(0x40, 0x80, 0xC0));
+
+        let faded = ClearAnimation::Fade.stage_images(&image);
+        assert_eq!(faded.len(), STAGE_COUNT);
+        assert_eq!(faded[0], image);
+        assert!(faded.windows(2).all(|pair| pair[0].pixels[0].a > pair[1].pixels[0].a));
+
+        let dissolved = ClearAnimation::Dissolve.stage_images(&image);
+        let visible_counts: Vec<usize> = dissolved.iter()
+            .map(|stage| stage.pixels.iter().filter(|p| p.a > 0).count())
+            .collect();
+        assert_eq!(visible_counts[0], 400);
+        assert!(visible_counts.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(visible_counts[STAGE_COUNT - 1] < 100);
+
+        let highlighted = ClearAnimation::Highlight.stage_images(&image);
+        assert_eq!(highlighted[0].pixels[0], Rgba::rgb(0x9F, 0xBF, 0xDF));
+        assert!(!ClearAnimation::Highlight.flashes());
+
+        assert_eq!(stage_index(DISAPPEAR_BLINK_COUNT, STAGE_COUNT), 0);
+        assert_eq!(stage_index(0, STAGE_COUNT), STAGE_COUNT - 1);
+        assert_eq!(stage_index(0, 1), 0);
+    }
+}
@@ -0,0 +1,140 @@
+//! Settings that are kept between runs.
+//!
+//! They are stored in the file `columns/config.txt` within the configuration directory of the
+//! user (`$XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%`) unless `--config` names another file. Each
+//! line has the form `key = value`; empty lines and lines starting with `#` are ignored. Settings
+//! given on the command line take precedence over the file. The keys are:
+//!
+//! * `clear_animation`: how the blocks of completed sequences disappear (`blink`, `fade`,
+//!   `shrink`, `dissolve` or `highlight`)
+//! * `no_flash`: `true` to avoid rapidly flashing animations
+//! * `effects`: the effects for clears and cascades, separated by commas (`particles`, `shake`,
+//!   `score-text` and `chain-label`); empty for none
+
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::clear_animation::ClearAnimation;
+use crate::effects::Effect;
+
+
+/// The settings, each of which may be missing from the file.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Config {
+    pub clear_animation: Option<ClearAnimation>,
+    pub no_flash: Option<bool>,
+    pub effects: Option<Vec<Effect>>,
+}
+impl Config {
+    /// Returns where the configuration is stored by default, if the configuration directory can
+    /// be determined.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(config_dir.join("columns").join("config.txt"))
+    }
+
+    /// Reads the configuration from the file; a missing file is an empty configuration.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut config = Self::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| invalid(format!("expected \"key = value\", got {:?}", line)))?;
+            let value = value.trim();
+            match key.trim() {
+                "clear_animation" => {
+                    let animation = ClearAnimation::from_str(value, true)
+                        .map_err(|_| invalid(format!("unknown clear animation {:?}", value)))?;
+                    config.clear_animation = Some(animation);
+                },
+                "no_flash" => {
+                    let no_flash = value.parse()
+                        .map_err(|_| invalid(format!("no_flash must be true or false, not {:?}", value)))?;
+                    config.no_flash = Some(no_flash);
+                },
+                "effects" => {
+                    let effects = value.split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| Effect::from_str(name, true)
+                            .map_err(|_| invalid(format!("unknown effect {:?}", name))))
+                        .collect::<io::Result<Vec<Effect>>>()?;
+                    config.effects = Some(effects);
+                },
+                other => return Err(invalid(format!("unknown setting {:?}", other))),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Writes the configuration into the file, creating its directory if necessary.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::from("# settings of Columns\n");
+        if let Some(animation) = self.clear_animation {
+            let name = animation.to_possible_value().unwrap();
+            text.push_str(&format!("clear_animation = {}\n", name.get_name()));
+        }
+        if let Some(no_flash) = self.no_flash {
+            text.push_str(&format!("no_flash = {}\n", no_flash));
+        }
+        if let Some(effects) = &self.effects {
+            let names: Vec<String> = effects.iter()
+                .map(|effect| effect.to_possible_value().unwrap().get_name().to_owned())
+                .collect();
+            text.push_str(&format!("effects = {}\n", names.join(", ")));
+        }
+        text
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::clear_animation::ClearAnimation;
+    use crate::effects::Effect;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse("# comment\n\nclear_animation = Dissolve\nno_flash=true\neffects = shake, score-text\n").unwrap();
+        assert_eq!(config.clear_animation, Some(ClearAnimation::Dissolve));
+        assert_eq!(config.no_flash, Some(true));
+        assert_eq!(config.effects, Some(vec![Effect::Shake, Effect::ScoreText]));
+        assert_eq!(Config::parse(&config.to_text()).unwrap(), config);
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("no_flash = maybe").is_err());
+        assert!(Config::parse("volume = 11").is_err());
+        assert_eq!(Config::parse("effects =").unwrap().effects, Some(Vec::new()));
+        assert!(Config::parse("effects = confetti").is_err());
+    }
+}
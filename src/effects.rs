@@ -0,0 +1,381 @@
+//! Effects that emphasize clears and cascades, each of which can be switched on separately.
+//!
+//! Like the smooth movement of the blocks, they are purely about drawing: the effects follow the
+//! game from frame to frame, notice when sequences are completed and when their blocks vanish, and
+//! count how many clears follow each other without a new piece (the chain depth).
+
+
+use std::f64::consts::TAU;
+
+use clap::ValueEnum;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+use crate::{
+    BLOCK_COLOR_COUNT, BLOCK_HEIGHT_PX, BLOCK_WIDTH_PX, FIELD_HEIGHT_BLOCKS, FIELD_OFFSET_LEFT_PX,
+    FIELD_OFFSET_TOP_PX, FIELD_WIDTH_BLOCKS,
+};
+use crate::game::{Game, rng_from_seed};
+use crate::model::Field;
+use crate::pixel_font::{GLYPH_HEIGHT, draw_text, text_width};
+use crate::render::{Renderer, Rgba};
+
+
+/// How many particles fly off each vanishing block.
+const PARTICLES_PER_BLOCK: u32 = 8;
+
+/// How many frames a particle lives.
+const PARTICLE_FRAMES: u32 = 40;
+
+const PARTICLE_SIZE_PX: u32 = 4;
+
+/// The fastest initial speed of a particle in pixels per frame.
+const PARTICLE_MAX_SPEED: f64 = 3.0;
+
+/// How much faster particles fall with every frame, in pixels per frame.
+const PARTICLE_GRAVITY: f64 = 0.15;
+
+/// How many frames the screen shakes after blocks vanish.
+const SHAKE_FRAMES: u32 = 15;
+
+/// How far the screen shakes at most per level of chain depth, in pixels.
+const SHAKE_PX_PER_CHAIN_DEPTH: f64 = 2.0;
+
+/// How far the screen shakes at most, however deep the chain.
+const SHAKE_MAX_PX: f64 = 10.0;
+
+/// How many frames a score text or chain label is shown.
+const TEXT_FRAMES: u32 = 60;
+
+/// How fast score texts rise, in pixels per frame.
+const TEXT_RISE_SPEED: f64 = 0.5;
+
+/// The size of the font pixels of score texts.
+const SCORE_TEXT_SCALE: u32 = 3;
+
+/// The size of the font pixels of chain labels.
+const CHAIN_LABEL_SCALE: u32 = 4;
+
+/// How far texts cast their shadow, in pixels.
+const TEXT_SHADOW_OFFSET_PX: i32 = 2;
+
+
+/// An effect that can be switched on.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, ValueEnum)]
+pub(crate) enum Effect {
+    /// Particles in the block colors fly off vanishing blocks.
+    Particles,
+
+    /// The screen shakes when blocks vanish, the more the deeper the chain.
+    Shake,
+
+    /// The points scored by completed sequences float up from them.
+    ScoreText,
+
+    /// Cascades are labeled with their chain depth, such as "2x CHAIN".
+    ChainLabel,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+struct Particle {
+    x: f64,
+    y: f64,
+    speed_x: f64,
+    speed_y: f64,
+    color_index: u8,
+    age: u32,
+}
+
+
+/// A text that is shown for a while, centered horizontally on `x`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct FloatingText {
+    text: String,
+    x: i32,
+    y: i32,
+    age: u32,
+}
+impl FloatingText {
+    /// Returns the opacity of the text, which fades out during the last third of its life.
+    fn alpha(&self) -> u8 {
+        let remaining = TEXT_FRAMES.saturating_sub(self.age);
+        let alpha = (0xFF * remaining / (TEXT_FRAMES / 3)).min(0xFF);
+        alpha.try_into().unwrap()
+    }
+
+    fn draw<R: Renderer>(&self, renderer: &mut R, y: i32, scale: u32, color: Rgba) {
+        let half_width = i32::try_from(text_width(&self.text) * scale / 2).unwrap();
+        let alpha = self.alpha();
+        let x = self.x - half_width;
+        draw_text(renderer, x + TEXT_SHADOW_OFFSET_PX, y + TEXT_SHADOW_OFFSET_PX, scale, Rgba::BLACK.with_alpha(alpha), &self.text);
+        draw_text(renderer, x, y, scale, color.with_alpha(alpha), &self.text);
+    }
+}
+
+
+/// The effects of a game, which follow the game from frame to frame.
+#[derive(Clone, Debug)]
+pub(crate) struct Effects {
+    enabled: Vec<Effect>,
+    rng: StdRng,
+
+    particles: Vec<Particle>,
+    score_texts: Vec<FloatingText>,
+    chain_label: Option<FloatingText>,
+
+    /// How far the screen shook at most when the shake started, how many frames of it are left
+    /// and by how much the screen is shifted in this frame.
+    shake_strength_px: f64,
+    shake_frames_left: u32,
+    shake_offset: (i32, i32),
+
+    /// How many clears have followed each other since the last piece was controlled.
+    chain_depth: u32,
+
+    /// The coordinates and colors of the blocks that are currently disappearing.
+    clearing_blocks: Vec<(u32, u32, u8)>,
+
+    /// The score at the frame that the effects have been updated to.
+    score: u64,
+
+    /// The frame of the game that the effects have been updated to.
+    frame_count: Option<u64>,
+}
+impl Effects {
+    pub fn new(enabled: &[Effect]) -> Self {
+        Self {
+            enabled: enabled.to_vec(),
+            rng: rng_from_seed(0),
+            particles: Vec::new(),
+            score_texts: Vec::new(),
+            chain_label: None,
+            shake_strength_px: 0.0,
+            shake_frames_left: 0,
+            shake_offset: (0, 0),
+            chain_depth: 0,
+            clearing_blocks: Vec::new(),
+            score: 0,
+            frame_count: None,
+        }
+    }
+
+    fn is_enabled(&self, effect: Effect) -> bool {
+        self.enabled.contains(&effect)
+    }
+
+    /// Follows the game to its current frame.
+    pub fn update(&mut self, game: &Game) {
+        if self.frame_count == Some(game.frame_count) {
+            return;
+        }
+        let elapsed_frames = match self.frame_count {
+            Some(previous) if previous < game.frame_count && game.score >= self.score => game.frame_count - previous,
+            Some(_) => {
+                // e.g. a new game; start over
+                *self = Self::new(&self.enabled);
+                0
+            },
+            None => 0,
+        };
+        let is_first_update = self.frame_count.is_none();
+        self.frame_count = Some(game.frame_count);
+
+        for _ in 0..elapsed_frames {
+            self.advance();
+        }
+
+        if !game.field.block_coords_with_predicate(|b| b.is_descending()).is_empty() {
+            // a new piece is being controlled
+            self.chain_depth = 0;
+        }
+
+        let clearing_blocks: Vec<(u32, u32, u8)> = game.field.blocks().iter()
+            .zip(Field::coords())
+            .filter_map(|(field_block, (x, y))| field_block.as_block()
+                .filter(|block| block.state.is_disappearing())
+                .map(|block| (x, y, block.color_index)))
+            .collect();
+        if !is_first_update {
+            if self.clearing_blocks.is_empty() && !clearing_blocks.is_empty() {
+                self.chain_depth += 1;
+                self.start_clear(&clearing_blocks, game.score.saturating_sub(self.score));
+            } else if !self.clearing_blocks.is_empty() && clearing_blocks.is_empty() {
+                let vanished_blocks = std::mem::take(&mut self.clearing_blocks);
+                self.vanish(&vanished_blocks);
+            }
+        }
+        self.clearing_blocks = clearing_blocks;
+        self.score = game.score;
+
+        let shake_px = self.shake_strength_px * f64::from(self.shake_frames_left) / f64::from(SHAKE_FRAMES);
+        self.shake_offset = if shake_px >= 1.0 {
+            let mut offset = || self.rng.gen_range(-shake_px..=shake_px).round() as i32;
+            (offset(), offset())
+        } else {
+            (0, 0)
+        };
+    }
+
+    /// Moves everything on by one frame and removes what has run its course.
+    fn advance(&mut self) {
+        for particle in &mut self.particles {
+            particle.x += particle.speed_x;
+            particle.y += particle.speed_y;
+            particle.speed_y += PARTICLE_GRAVITY;
+            particle.age += 1;
+        }
+        self.particles.retain(|particle| particle.age < PARTICLE_FRAMES);
+
+        for text in self.score_texts.iter_mut().chain(self.chain_label.as_mut()) {
+            text.age += 1;
+        }
+        self.score_texts.retain(|text| text.age < TEXT_FRAMES);
+        if self.chain_label.as_ref().is_some_and(|label| label.age >= TEXT_FRAMES) {
+            self.chain_label = None;
+        }
+
+        self.shake_frames_left = self.shake_frames_left.saturating_sub(1);
+    }
+
+    /// Shows the points and the chain depth of sequences that have just been completed.
+    fn start_clear(&mut self, clearing_blocks: &[(u32, u32, u8)], points: u64) {
+        if self.is_enabled(Effect::ScoreText) && points > 0 {
+            let block_count = clearing_blocks.len() as f64;
+            let center_x = clearing_blocks.iter().map(|&(x, y, _)| block_center(x, y).0).sum::<f64>() / block_count;
+            let center_y = clearing_blocks.iter().map(|&(x, y, _)| block_center(x, y).1).sum::<f64>() / block_count;
+            self.score_texts.push(FloatingText {
+                text: format!("+{}", points),
+                x: center_x.round() as i32,
+                y: center_y.round() as i32 - i32::try_from(GLYPH_HEIGHT * SCORE_TEXT_SCALE / 2).unwrap(),
+                age: 0,
+            });
+        }
+
+        if self.is_enabled(Effect::ChainLabel) && self.chain_depth >= 2 {
+            let field_width_px = i32::try_from(FIELD_WIDTH_BLOCKS * BLOCK_WIDTH_PX).unwrap();
+            let field_height_px = i32::try_from(FIELD_HEIGHT_BLOCKS * BLOCK_HEIGHT_PX).unwrap();
+            self.chain_label = Some(FloatingText {
+                text: format!("{}x CHAIN", self.chain_depth),
+                x: FIELD_OFFSET_LEFT_PX + field_width_px / 2,
+                y: FIELD_OFFSET_TOP_PX + field_height_px / 4,
+                age: 0,
+            });
+        }
+    }
+
+    /// Bursts the blocks that have just vanished into particles and shakes the screen.
+    fn vanish(&mut self, vanished_blocks: &[(u32, u32, u8)]) {
+        if self.is_enabled(Effect::Particles) {
+            for &(x, y, color_index) in vanished_blocks {
+                let (center_x, center_y) = block_center(x, y);
+                for _ in 0..PARTICLES_PER_BLOCK {
+                    let angle = self.rng.gen_range(0.0..TAU);
+                    let speed = self.rng.gen_range(0.5..PARTICLE_MAX_SPEED);
+                    self.particles.push(Particle {
+                        x: center_x,
+                        y: center_y,
+                        speed_x: speed * angle.cos(),
+                        // a little upwards so that they scatter before they fall
+                        speed_y: speed * angle.sin() - 1.5,
+                        color_index,
+                        age: 0,
+                    });
+                }
+            }
+        }
+
+        if self.is_enabled(Effect::Shake) {
+            self.shake_strength_px = (f64::from(self.chain_depth) * SHAKE_PX_PER_CHAIN_DEPTH).min(SHAKE_MAX_PX);
+            self.shake_frames_left = SHAKE_FRAMES;
+        }
+    }
+
+    /// Returns by how much the whole picture is shifted in this frame to shake the screen.
+    pub fn shake_offset(&self) -> (i32, i32) {
+        self.shake_offset
+    }
+
+    /// Draws the particles and texts in the colors of the palette.
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, palette: &[Rgba; BLOCK_COLOR_COUNT]) {
+        for particle in &self.particles {
+            let remaining = PARTICLE_FRAMES - particle.age;
+            let alpha = u8::try_from(0xFF * remaining / PARTICLE_FRAMES).unwrap();
+            let half_size = f64::from(PARTICLE_SIZE_PX / 2);
+            renderer.fill_rect(
+                (particle.x - half_size).round() as i32,
+                (particle.y - half_size).round() as i32,
+                PARTICLE_SIZE_PX,
+                PARTICLE_SIZE_PX,
+                palette[usize::from(particle.color_index)].with_alpha(alpha),
+            );
+        }
+
+        for text in &self.score_texts {
+            let y = text.y - (f64::from(text.age) * TEXT_RISE_SPEED).round() as i32;
+            text.draw(renderer, y, SCORE_TEXT_SCALE, Rgba::WHITE);
+        }
+
+        if let Some(label) = &self.chain_label {
+            label.draw(renderer, label.y, CHAIN_LABEL_SCALE, Rgba::YELLOW);
+        }
+    }
+}
+
+
+/// Returns the middle of the cell in pixels.
+fn block_center(x: u32, y: u32) -> (f64, f64) {
+    (
+        f64::from(FIELD_OFFSET_LEFT_PX) + f64::from(x * BLOCK_WIDTH_PX) + f64::from(BLOCK_WIDTH_PX) / 2.0,
+        f64::from(FIELD_OFFSET_TOP_PX) + f64::from(y * BLOCK_HEIGHT_PX) + f64::from(BLOCK_HEIGHT_PX) / 2.0,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Effect, Effects, SHAKE_FRAMES};
+    use crate::game::Game;
+    use crate::model::{Block, BlockState, FieldBlock};
+
+    #[test]
+    fn test_chain() {
+        let all_effects = [Effect::Particles, Effect::Shake, Effect::ScoreText, Effect::ChainLabel];
+        let mut game = Game::new(0);
+        let mut effects = Effects::new(&all_effects);
+        effects.update(&game);
+
+        for chain_depth in 1..=2 {
+            // complete a vertical sequence at the bottom
+            for y in 15..18 {
+                *game.field.block_by_coord_mut(0, y) = FieldBlock::Block(Block {
+                    color_index: 1,
+                    state: BlockState::Stationary,
+                });
+            }
+            let score_before = game.score;
+            game.block_fall_counter = game.block_fall_limit;
+            game.tick();
+            effects.update(&game);
+            assert_eq!(effects.chain_depth, chain_depth);
+            assert_eq!(effects.score_texts.last().unwrap().text, format!("+{}", game.score - score_before));
+            assert_eq!(effects.chain_label.is_some(), chain_depth == 2);
+
+            // wait until the blocks have vanished
+            while !game.field.block_coords_with_predicate(|b| b.is_disappearing()).is_empty() {
+                game.tick();
+                effects.update(&game);
+            }
+            assert_eq!(effects.particles.iter().filter(|p| p.age == 0).count(), 3 * 8);
+            assert_eq!(effects.shake_frames_left, SHAKE_FRAMES);
+        }
+
+        // everything runs its course
+        for _ in 0..100 {
+            game.tick();
+            effects.update(&game);
+        }
+        assert!(effects.particles.is_empty());
+        assert_eq!(effects.shake_offset(), (0, 0));
+    }
+}
@@ -0,0 +1,432 @@
+//! A reinforcement-learning environment on top of the headless game.
+//!
+//! The environment follows the conventions of Gym: [`Env::reset`] starts a new episode and returns
+//! the first observation, while [`Env::step`] places the current piece according to an action and
+//! returns the next observation along with the reward (the score gained), whether the episode is
+//! over and further information.
+//!
+//! [`serve`] makes a batch of environments available to other processes via a line-based protocol
+//! on standard input and output. The commands are:
+//!
+//! * `reset SEED...`: starts a new episode in each environment using one seed per environment
+//! * `step COLUMN ROTATION...`: places the piece in each environment using one column and rotation
+//!   (as in the bot protocol, so the rotation must be less than the length of the piece) per
+//!   environment
+//! * `quit`: stops serving
+//!
+//! For each environment, the response to `reset` and `step` consists of the line `env INDEX`, for
+//! `step` followed by `reward N`, `done 0|1`, `score N` and `moved 0|1` (whether the piece could
+//! be moved as requested), and then the observation in the same format as the bot protocol (`field`,
+//! `piece` and `queue`). The response ends with the line `ok`; errors are reported as `error
+//! MESSAGE`.
+
+
+use std::io::{self, BufRead, Write};
+use std::thread;
+
+use crate::{FIELD_BLOCK_COUNT, FIELD_HEIGHT_BLOCKS, FIELD_WIDTH_BLOCKS, NEW_BLOCK_COLUMN, PIECE_LENGTH};
+use crate::ai::BestMove;
+use crate::game::{Game, GameState, Piece};
+use crate::model::{Field, FieldBlock};
+
+
+/// What an agent gets to see of the game.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Observation {
+    /// The color index of each block in the field, row by row from the top, or -1 for empty cells.
+    ///
+    /// The descending piece is not part of the field.
+    pub field: Vec<i8>,
+
+    /// The column containing the descending piece.
+    pub piece_column: u32,
+
+    /// The color indexes of the descending piece from top to bottom; empty if there is none.
+    pub piece: Vec<u8>,
+
+    /// The color indexes of the upcoming pieces from top to bottom.
+    ///
+    /// Since the game does not preview pieces, this is currently always empty.
+    pub queue: Vec<Vec<u8>>,
+}
+impl Observation {
+    pub fn from_field(field: &Field) -> Self {
+        let mut field_colors = Vec::with_capacity(FIELD_BLOCK_COUNT);
+        for field_block in field.blocks() {
+            match field_block {
+                FieldBlock::Block(block) if !block.state.is_descending() => {
+                    field_colors.push(i8::try_from(block.color_index).unwrap());
+                },
+                _ => field_colors.push(-1),
+            }
+        }
+
+        let (piece_column, piece) = match Piece::from_field(field) {
+            Some(p) => (p.column, p.colors),
+            None => (NEW_BLOCK_COLUMN, Vec::new()),
+        };
+
+        Self {
+            field: field_colors,
+            piece_column,
+            piece,
+            queue: Vec::new(),
+        }
+    }
+
+    /// Returns the field in the encoding of the bot protocol, without line breaks between the rows.
+    pub fn field_string(&self) -> String {
+        self.field.iter()
+            .map(|&color| match u32::try_from(color) {
+                Ok(c) => char::from_digit(c, 10).unwrap(),
+                Err(_) => '.',
+            })
+            .collect()
+    }
+
+    /// Writes the observation in the encoding of the bot protocol.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "field {} {}", FIELD_WIDTH_BLOCKS, FIELD_HEIGHT_BLOCKS)?;
+        let field_string = self.field_string();
+        let width: usize = FIELD_WIDTH_BLOCKS.try_into().unwrap();
+        for y in 0..FIELD_HEIGHT_BLOCKS.try_into().unwrap() {
+            writeln!(writer, "{}", &field_string[y*width..(y+1)*width])?;
+        }
+
+        write!(writer, "piece {}", self.piece_column)?;
+        for color in &self.piece {
+            write!(writer, " {}", color)?;
+        }
+        writeln!(writer)?;
+
+        write!(writer, "queue")?;
+        for queued_piece in &self.queue {
+            write!(writer, " ")?;
+            for color in queued_piece {
+                write!(writer, "{}", color)?;
+            }
+        }
+        writeln!(writer)
+    }
+}
+
+
+/// Additional information about a step.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct StepInfo {
+    /// The total score of the episode so far.
+    pub score: u64,
+
+    /// How many pieces have been placed during the episode so far.
+    pub pieces_placed: u64,
+
+    /// Whether the piece could be moved into the requested column; if not, it was dropped as far
+    /// as it could be moved.
+    pub moved_as_requested: bool,
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct StepResult {
+    pub observation: Observation,
+    pub reward: u64,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+
+/// A single environment playing one game at a time.
+pub(crate) struct Env {
+    game: Game,
+    pieces_placed: u64,
+}
+impl Env {
+    pub fn new() -> Self {
+        let mut game = Game::new(0);
+        game.state = GameState::Over;
+        Self {
+            game,
+            pieces_placed: 0,
+        }
+    }
+
+    /// Starts a new episode with the given seed and returns the first observation.
+    pub fn reset(&mut self, seed: u128) -> Observation {
+        self.game = Game::new(seed);
+        self.pieces_placed = 0;
+        self.game.run_until_next_piece();
+        Observation::from_field(&self.game.field)
+    }
+
+    /// Places the descending piece as described by the action and advances the game until the next
+    /// piece appears.
+    pub fn step(&mut self, action: &BestMove) -> StepResult {
+        if self.game.state != GameState::Play {
+            // episode is already over
+            return StepResult {
+                observation: Observation::from_field(&self.game.field),
+                reward: 0,
+                done: true,
+                info: StepInfo {
+                    score: self.game.score,
+                    pieces_placed: self.pieces_placed,
+                    moved_as_requested: false,
+                },
+            };
+        }
+
+        let old_score = self.game.score;
+
+        self.game.apply_move(action);
+        let moved_as_requested = self.game.field
+            .block_coords_with_predicate(|b| b.is_descending())
+            .iter()
+            .all(|&(x, _y)| x == action.column);
+        self.game.drop_piece();
+        self.pieces_placed += 1;
+
+        self.game.run_until_next_piece();
+
+        StepResult {
+            observation: Observation::from_field(&self.game.field),
+            reward: self.game.score - old_score,
+            done: self.game.state == GameState::Over,
+            info: StepInfo {
+                score: self.game.score,
+                pieces_placed: self.pieces_placed,
+                moved_as_requested,
+            },
+        }
+    }
+}
+impl Default for Env {
+    fn default() -> Self {
+        Env::new()
+    }
+}
+
+
+/// A batch of environments that are reset and stepped together.
+pub(crate) struct VecEnv {
+    envs: Vec<Env>,
+    thread_count: usize,
+}
+impl VecEnv {
+    pub fn new(count: usize, thread_count: usize) -> Self {
+        Self {
+            envs: (0..count).map(|_| Env::new()).collect(),
+            thread_count,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    /// Resets each environment with its corresponding seed.
+    pub fn reset(&mut self, seeds: &[u128]) -> Vec<Observation> {
+        assert_eq!(seeds.len(), self.envs.len());
+        self.envs.iter_mut()
+            .zip(seeds)
+            .map(|(env, &seed)| env.reset(seed))
+            .collect()
+    }
+
+    /// Steps each environment with its corresponding action, spreading the environments across
+    /// threads.
+    pub fn step(&mut self, actions: &[BestMove]) -> Vec<StepResult> {
+        assert_eq!(actions.len(), self.envs.len());
+        if self.thread_count <= 1 || self.envs.len() <= 1 {
+            return self.envs.iter_mut()
+                .zip(actions)
+                .map(|(env, action)| env.step(action))
+                .collect();
+        }
+
+        let chunk_size = self.envs.len().div_ceil(self.thread_count);
+        thread::scope(|scope| {
+            let handles: Vec<_> = self.envs
+                .chunks_mut(chunk_size)
+                .zip(actions.chunks(chunk_size))
+                .map(|(env_chunk, action_chunk)| scope.spawn(move || {
+                    env_chunk.iter_mut()
+                        .zip(action_chunk)
+                        .map(|(env, action)| env.step(action))
+                        .collect::<Vec<StepResult>>()
+                }))
+                .collect();
+
+            // join in order of the chunks to keep the results in order of the environments
+            handles.into_iter()
+                .flat_map(|handle| handle.join().expect("environment thread panicked"))
+                .collect()
+        })
+    }
+}
+
+
+fn parse_args<T: std::str::FromStr>(args: &[&str], expected_count: usize) -> Result<Vec<T>, String> {
+    if args.len() != expected_count {
+        return Err(format!("expected {} arguments, got {}", expected_count, args.len()));
+    }
+    args.iter()
+        .map(|arg| arg.parse().map_err(|_| format!("invalid argument {:?}", arg)))
+        .collect()
+}
+
+
+/// Serves a batch of environments on standard input and output until `quit` is received or the
+/// input ends.
+pub(crate) fn serve(count: usize, thread_count: usize) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    serve_on(count, thread_count, stdin.lock(), stdout.lock())
+}
+
+
+/// Serves a batch of environments on the given input and output until `quit` is received or the
+/// input ends.
+fn serve_on<R: BufRead, W: Write>(count: usize, thread_count: usize, input: R, mut out: W) -> io::Result<()> {
+    let mut vec_env = VecEnv::new(count, thread_count);
+
+    for line_res in input.lines() {
+        let line = line_res?;
+        let mut pieces = line.split_whitespace();
+        let command = match pieces.next() {
+            Some(c) => c,
+            None => continue,
+        };
+        let args: Vec<&str> = pieces.collect();
+
+        match command {
+            "reset" => {
+                match parse_args::<u128>(&args, vec_env.len()) {
+                    Ok(seeds) => {
+                        let observations = vec_env.reset(&seeds);
+                        for (i, observation) in observations.iter().enumerate() {
+                            writeln!(out, "env {}", i)?;
+                            observation.write(&mut out)?;
+                        }
+                        writeln!(out, "ok")?;
+                    },
+                    Err(e) => writeln!(out, "error {}", e)?,
+                }
+            },
+            "step" => {
+                let parsed = parse_args::<u32>(&args, 2*vec_env.len())
+                    .and_then(|numbers| numbers.chunks(2)
+                        .map(|pair| {
+                            let rotate_count = usize::try_from(pair[1]).ok()
+                                .filter(|&r| r < PIECE_LENGTH);
+                            if pair[0] >= FIELD_WIDTH_BLOCKS {
+                                Err(format!("column {} out of range", pair[0]))
+                            } else if let Some(rotate_count) = rotate_count {
+                                Ok(BestMove {
+                                    column: pair[0],
+                                    rotate_count,
+                                })
+                            } else {
+                                Err(format!("rotation {} out of range", pair[1]))
+                            }
+                        })
+                        .collect::<Result<Vec<BestMove>, String>>()
+                    );
+                match parsed {
+                    Ok(actions) => {
+                        let results = vec_env.step(&actions);
+                        for (i, result) in results.iter().enumerate() {
+                            writeln!(out, "env {}", i)?;
+                            writeln!(out, "reward {}", result.reward)?;
+                            writeln!(out, "done {}", u8::from(result.done))?;
+                            writeln!(out, "score {}", result.info.score)?;
+                            writeln!(out, "moved {}", u8::from(result.info.moved_as_requested))?;
+                            result.observation.write(&mut out)?;
+                        }
+                        writeln!(out, "ok")?;
+                    },
+                    Err(e) => writeln!(out, "error {}", e)?,
+                }
+            },
+            "quit" => break,
+            other => writeln!(out, "error unknown command {:?}", other)?,
+        }
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Env, VecEnv, serve_on};
+    use crate::{FIELD_BLOCK_COUNT, NEW_BLOCK_COLUMN};
+    use crate::ai::BestMove;
+
+    #[test]
+    fn test_env() {
+        let seeds = [1, 2, 3];
+        let actions = [
+            BestMove { column: 0, rotate_count: 1 },
+            BestMove { column: 5, rotate_count: 0 },
+            BestMove { column: 2, rotate_count: 2 },
+        ];
+
+        // a step after a reset continues from the observation of the reset
+        let mut env = Env::new();
+        let observation = env.reset(seeds[1]);
+        assert_eq!(observation.field, vec![-1; FIELD_BLOCK_COUNT]);
+        assert_eq!(observation.piece_column, NEW_BLOCK_COLUMN);
+        assert_eq!(observation.piece.len(), 3);
+        let result = env.step(&actions[0]);
+        assert!(!result.done);
+        assert_eq!(result.reward, 0);
+        assert_eq!(result.info.score, 0);
+        assert_eq!(result.info.pieces_placed, 1);
+        assert!(result.info.moved_as_requested);
+
+        // the rotated piece lies at the bottom of the first column
+        let placed_colors: Vec<i8> = result.observation.field.iter()
+            .copied()
+            .filter(|&color| color >= 0)
+            .collect();
+        let mut expected_colors: Vec<i8> = observation.piece.iter()
+            .map(|&color| i8::try_from(color).unwrap())
+            .collect();
+        expected_colors.rotate_right(1);
+        assert_eq!(placed_colors, expected_colors);
+        assert_eq!(result.observation.field[FIELD_BLOCK_COUNT - 6], expected_colors[2]);
+
+        // the batch behaves like the environments one after another
+        let mut sequential_results = Vec::new();
+        for (&seed, action) in seeds.iter().zip(&actions) {
+            let mut env = Env::new();
+            let observation = env.reset(seed);
+            sequential_results.push((observation, env.step(action), env.step(action)));
+        }
+        let mut vec_env = VecEnv::new(seeds.len(), 2);
+        let observations = vec_env.reset(&seeds);
+        let first_results = vec_env.step(&actions);
+        let second_results = vec_env.step(&actions);
+        for (i, (observation, first_result, second_result)) in sequential_results.into_iter().enumerate() {
+            assert_eq!(observations[i], observation);
+            assert_eq!(first_results[i], first_result);
+            assert_eq!(second_results[i], second_result);
+        }
+
+        // the protocol
+        let mut output = Vec::new();
+        serve_on(2, 1, "reset 1 2\nstep 0 1 6 0\nstep 0 4294967295 5 0\nstep 0 -1 5 0\nstep 0 1 5 0\nquit\nreset 1 2\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "env 0");
+        assert_eq!(lines[1], "field 6 18");
+        assert_eq!(lines.iter().filter(|&&line| line == "ok").count(), 2);
+        assert!(lines.contains(&"error column 6 out of range"));
+        assert!(lines.contains(&"error rotation 4294967295 out of range"));
+        assert_eq!(lines.iter().filter(|line| line.starts_with("error")).count(), 3);
+        assert!(lines.contains(&"reward 0"));
+        assert!(lines.contains(&"moved 1"));
+        assert_eq!(lines.last(), Some(&"ok"));
+    }
+}
@@ -0,0 +1,287 @@
+//! Exporting games from record files as animations.
+//!
+//! A recorded game is replayed frame by frame: each piece appears as recorded, is moved and
+//! rotated into place like the AI does on the title screen and is then dropped, after which the
+//! cascades play out as in the game. Every frame is drawn offscreen the same way as the window
+//! does and written either into an animated GIF or as numbered PNG files, which tools like ffmpeg
+//! can turn into videos.
+
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::{DEFAULT_BLOCK_FALL_LIMIT, DEMO_DELAY_FRAMES, DEMO_FRAMES_PER_ACTION, SCORE_SPEEDUP_DIVISOR};
+use crate::autoplay::AnimatedMove;
+use crate::effects::Effects;
+use crate::game::{Game, GameEvent};
+use crate::motion::Motion;
+use crate::record::{Decision, read_decisions};
+use crate::render::software::Image;
+
+
+/// The number of frames the game advances per second.
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// How long the last frame of a game that is over is shown in a GIF, in hundredths of a second.
+const GAME_OVER_HOLD_CENTISECONDS: u16 = 200;
+
+
+/// Which part of a record file is exported and how.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Settings {
+    /// The index of the game in the record file.
+    pub game: u64,
+
+    /// The index of the first move that is shown.
+    pub first_move: u64,
+
+    /// The index of the last move that is shown, or `None` to show the game until its end.
+    pub last_move: Option<u64>,
+
+    /// How many frames are left out after each frame that is written.
+    pub frame_skip: u64,
+
+    /// The size of the frames in percent of the size of the window.
+    pub scale_percent: u32,
+}
+
+
+/// Where the frames end up.
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+
+        /// The number of hundredths of a second covered by the frames written so far.
+        centiseconds_written: u64,
+    },
+    Pngs {
+        dir: PathBuf,
+    },
+}
+impl Output {
+    fn create(path: &Path, width: u32, height: u32) -> io::Result<Self> {
+        let is_gif = path.extension()
+            .map(|ext| ext.eq_ignore_ascii_case("gif"))
+            .unwrap_or(false);
+        if is_gif {
+            let writer = BufWriter::new(File::create(path)?);
+            let mut encoder = gif::Encoder::new(writer, width.try_into().unwrap(), height.try_into().unwrap(), &[])
+                .map_err(io::Error::other)?;
+            encoder.set_repeat(gif::Repeat::Infinite)
+                .map_err(io::Error::other)?;
+            Ok(Self::Gif {
+                encoder,
+                centiseconds_written: 0,
+            })
+        } else {
+            fs::create_dir_all(path)?;
+            Ok(Self::Pngs {
+                dir: path.to_owned(),
+            })
+        }
+    }
+
+    /// Writes the frame with the given index, which is shown until the frame with the index
+    /// `next_game_frame` of the game (plus the extra time).
+    fn write_frame(&mut self, image: &Image, index: u64, next_game_frame: u64, extra_centiseconds: u16) -> io::Result<()> {
+        match self {
+            Self::Gif { encoder, centiseconds_written } => {
+                // keep the rounding errors of the delays from adding up; GIFs can't show a frame
+                // for longer than u16::MAX centiseconds
+                let until = next_game_frame * 100 / FRAMES_PER_SECOND;
+                let delay = u16::try_from(until - *centiseconds_written)
+                    .unwrap_or(u16::MAX)
+                    .saturating_add(extra_centiseconds);
+                *centiseconds_written = until;
+
+                let mut frame = gif_frame(image);
+                frame.delay = delay;
+                encoder.write_frame(&frame)
+                    .map_err(io::Error::other)
+            },
+            Self::Pngs { dir } => {
+                image.save(&dir.join(format!("frame-{:05}.png", index)))
+            },
+        }
+    }
+}
+
+
+/// Converts the image into a GIF frame, using its exact colors if there are few enough.
+fn gif_frame(image: &Image) -> gif::Frame<'static> {
+    let width: u16 = image.width.try_into().unwrap();
+    let height: u16 = image.height.try_into().unwrap();
+
+    let mut palette = Vec::new();
+    let mut color_to_index = HashMap::new();
+    let mut indexes = Vec::with_capacity(image.pixels.len());
+    for pixel in &image.pixels {
+        let index = match color_to_index.get(pixel) {
+            Some(&index) => index,
+            None => {
+                let index = match u8::try_from(color_to_index.len()) {
+                    Ok(index) => index,
+                    Err(_) => {
+                        // too many colors; let the encoder choose a palette
+                        let rgb: Vec<u8> = image.pixels.iter()
+                            .flat_map(|p| [p.r, p.g, p.b])
+                            .collect();
+                        return gif::Frame::from_rgb_speed(width, height, &rgb, 10);
+                    },
+                };
+                color_to_index.insert(*pixel, index);
+                palette.extend_from_slice(&[pixel.r, pixel.g, pixel.b]);
+                index
+            },
+        };
+        indexes.push(index);
+    }
+    gif::Frame::from_palette_pixels(width, height, indexes, palette, None)
+}
+
+
+/// Replays the moves, which must be consecutive moves of the same game, and passes every frame to
+/// the function along with whether the game is over.
+fn replay_frames<F: FnMut(&Game, bool) -> io::Result<()>>(decisions: &[Decision], start_score: u64, mut emit: F) -> io::Result<()> {
+    let mut game = Game::new(0);
+    game.score = start_score;
+
+    // the game speeds up as the score increases
+    game.block_fall_limit = DEFAULT_BLOCK_FALL_LIMIT
+        .saturating_sub(start_score / SCORE_SPEEDUP_DIVISOR)
+        .max(1);
+
+    for decision in decisions {
+        game.field = decision.field_with_piece();
+        for &color_index in &decision.piece {
+            game.color_stats[usize::from(color_index)] += 1;
+        }
+        let mut animated_move = Some(AnimatedMove::new(&decision.best_move, DEMO_FRAMES_PER_ACTION, DEMO_DELAY_FRAMES, true));
+        emit(&game, false)?;
+
+        loop {
+            match game.tick() {
+                Some(GameEvent::PieceSpawned) => {
+                    // the random piece is replaced by the next recorded piece
+                    if let Some(piece) = game.spawned_piece.take() {
+                        for color_index in piece.colors {
+                            let stat = &mut game.color_stats[usize::from(color_index)];
+                            *stat = stat.saturating_sub(1);
+                        }
+                    }
+                    break;
+                },
+                Some(GameEvent::GameOver) => {
+                    return emit(&game, true);
+                },
+                None => {},
+            }
+            if let Some(am) = animated_move.as_mut() {
+                if am.tick(&mut game) {
+                    animated_move = None;
+                }
+            }
+            emit(&game, false)?;
+        }
+    }
+    Ok(())
+}
+
+
+/// Replays the game from the record file and writes it into a GIF file if the output path ends in
+/// `.gif` and as numbered PNG files into the output directory otherwise. Returns the number of
+/// frames written.
+pub(crate) fn export(record_path: &Path, output_path: &Path, settings: &Settings) -> io::Result<u64> {
+    if settings.scale_percent == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the scale must be positive"));
+    }
+
+    let mut decisions: Vec<Decision> = read_decisions(record_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to read {}: {}", record_path.display(), e)))?
+        .into_iter()
+        .filter(|d| d.game == settings.game)
+        .collect();
+    decisions.sort_by_key(|d| d.move_index);
+    let start_score = decisions.iter()
+        .filter(|d| d.move_index < settings.first_move)
+        .map(|d| d.score_delta)
+        .sum();
+    decisions.retain(|d| {
+        d.move_index >= settings.first_move
+            && settings.last_move.map(|last| d.move_index <= last).unwrap_or(true)
+    });
+    if decisions.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no moves of game {} in the given range found in record file", settings.game),
+        ));
+    }
+
+    let width = (crate::WINDOW_WIDTH * settings.scale_percent / 100).max(1);
+    let height = (crate::WINDOW_HEIGHT * settings.scale_percent / 100).max(1);
+    let write_error = |e: io::Error| io::Error::new(e.kind(), format!("failed to write {}: {}", output_path.display(), e));
+    let mut output = Output::create(output_path, width, height)
+        .map_err(write_error)?;
+
+    let frame_step = settings.frame_skip + 1;
+    let mut game_frame = 0;
+    let mut written = 0;
+    let mut motion = Motion::new();
+    let mut effects = Effects::new(&crate::OPTS.get().expect("OPTS not set?!").effects());
+    let sprites = crate::screenshot::scaled_sprites((width, height));
+    replay_frames(&decisions, start_score, |game, is_over| {
+        motion.update(game);
+        effects.update(game);
+        if game_frame % frame_step == 0 || is_over {
+            let image = crate::screenshot::render_scaled(game, &motion, &effects, &[], None, &sprites, (width, height));
+            let extra = if is_over { GAME_OVER_HOLD_CENTISECONDS } else { 0 };
+            output.write_frame(&image, written, game_frame + frame_step, extra)
+                .map_err(write_error)?;
+            written += 1;
+        }
+        game_frame += 1;
+        Ok(())
+    })?;
+    Ok(written)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::replay_frames;
+    use crate::ai::BestMove;
+    use crate::record::{Decision, Player};
+
+    #[test]
+    fn test_replay_frames() {
+        // the piece completes a horizontal sequence at the bottom
+        let decision = Decision {
+            game: 0,
+            move_index: 0,
+            player: Player::Human,
+            field: format!("{}11....", ".".repeat(102)),
+            piece: vec![2, 3, 1],
+            best_move: BestMove { column: 2, rotate_count: 0 },
+            score_delta: 3,
+            final_score: 3,
+            game_over: false,
+        };
+
+        let mut frame_count = 0;
+        let mut saw_disappearing = false;
+        let mut last_score = 0;
+        replay_frames(&[decision], 0, |game, is_over| {
+            assert!(!is_over);
+            frame_count += 1;
+            saw_disappearing |= !game.field.block_coords_with_predicate(|b| b.is_disappearing()).is_empty();
+            last_score = game.score;
+            Ok(())
+        }).unwrap();
+
+        assert!(frame_count > 1);
+        assert!(saw_disappearing);
+        assert!(last_score > 0);
+    }
+}
@@ -0,0 +1,40 @@
+//! The ways in which the game can be shown and controlled.
+
+
+#[cfg(feature = "sdl")]
+pub(crate) mod sdl;
+pub(crate) mod tui;
+
+
+use crate::game::Game;
+
+
+/// An action requested by the player.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Input {
+    Quit,
+    Left,
+    Right,
+    Rotate,
+    Drop,
+    NewGame,
+    ToggleHint,
+    TogglePause,
+    Screenshot,
+
+    /// Any other key, which only matters on the title screen.
+    OtherKey,
+
+    /// What is shown has to be drawn again, e.g. because the window was uncovered.
+    Redraw,
+}
+
+
+/// Shows the game to the player and passes on their input.
+pub(crate) trait Frontend {
+    /// Returns the input received since the previous call, oldest first.
+    fn poll_inputs(&mut self) -> Vec<Input>;
+
+    /// Shows the current state of the game.
+    fn draw(&mut self, game: &Game, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>);
+}
@@ -0,0 +1,104 @@
+use sdl2::EventPump;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+use crate::{BLOCK_HEIGHT_PX, BLOCK_WIDTH_PX, OPTS, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::frontend::{Frontend, Input};
+use crate::effects::Effects;
+use crate::game::Game;
+use crate::motion::Motion;
+use crate::render::Renderer;
+use crate::render::scaled::{Layout, ScaledRenderer};
+use crate::render::sdl::SdlRenderer;
+use crate::theme::ThemeSprites;
+
+
+/// Shows the game in a window, scaled to the size of the window.
+pub(crate) struct SdlFrontend<'a> {
+    event_pump: EventPump,
+    renderer: SdlRenderer<'a, Window>,
+
+    /// The sprites of the theme, which are recreated in the size of the blocks when the window is
+    /// resized so that generated blocks stay sharp.
+    sprites: Option<ThemeSprites<Texture<'a>>>,
+    block_texture_size: (u32, u32),
+
+    motion: Motion,
+    effects: Effects,
+}
+impl<'a> SdlFrontend<'a> {
+    pub fn new(event_pump: EventPump, canvas: Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let renderer = SdlRenderer::new(canvas, texture_creator);
+        Self {
+            event_pump,
+            renderer,
+            sprites: None,
+            block_texture_size: (0, 0),
+            motion: Motion::new(),
+            effects: Effects::new(&OPTS.get().expect("OPTS not set?!").effects()),
+        }
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let window = self.renderer.window_mut();
+        let new_state = if window.fullscreen_state() == FullscreenType::Off {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        window.set_fullscreen(new_state).unwrap();
+    }
+}
+impl<'a> Frontend for SdlFrontend<'a> {
+    fn poll_inputs(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit { .. } => inputs.push(Input::Quit),
+                Event::KeyDown { keycode: Some(kc), .. } => {
+                    let input = match kc {
+                        Keycode::Escape => Input::Quit,
+                        Keycode::Left|Keycode::A|Keycode::J => Input::Left,
+                        Keycode::Right|Keycode::D|Keycode::L => Input::Right,
+                        Keycode::Up|Keycode::W|Keycode::I => Input::Rotate,
+                        Keycode::Down|Keycode::S|Keycode::K => Input::Drop,
+                        Keycode::F2 => Input::NewGame,
+                        Keycode::H => Input::ToggleHint,
+                        Keycode::F3 => Input::TogglePause,
+                        Keycode::F11 => {
+                            // handled by the window itself; not a key that starts a game
+                            self.toggle_fullscreen();
+                            Input::Redraw
+                        },
+                        Keycode::F12 => Input::Screenshot,
+                        _ => Input::OtherKey,
+                    };
+                    inputs.push(input);
+                },
+                Event::Window { win_event: WindowEvent::Exposed|WindowEvent::SizeChanged(_, _), .. } => inputs.push(Input::Redraw),
+                _ => {},
+            }
+        }
+        inputs
+    }
+
+    fn draw(&mut self, game: &Game, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>) {
+        let opts = OPTS.get().expect("OPTS not set?!");
+        let layout = Layout::new((WINDOW_WIDTH, WINDOW_HEIGHT), self.renderer.output_size(), opts.integer_scaling);
+
+        let block_size = (layout.length(BLOCK_WIDTH_PX), layout.length(BLOCK_HEIGHT_PX));
+        if self.sprites.is_none() || block_size != self.block_texture_size {
+            self.sprites = Some(ThemeSprites::new(&mut self.renderer, crate::theme::current(), block_size.0, block_size.1));
+            self.block_texture_size = block_size;
+        }
+
+        self.motion.update(game);
+        self.effects.update(game);
+        let (shake_x, shake_y) = self.effects.shake_offset();
+        let mut renderer = ScaledRenderer::new(&mut self.renderer, layout.shifted(shake_x, shake_y));
+        crate::draw(&mut renderer, game, &self.motion, &self.effects, ghost_blocks, hints_remaining, self.sprites.as_ref().unwrap());
+    }
+}
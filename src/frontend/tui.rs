@@ -0,0 +1,297 @@
+//! A frontend that draws the game with colored text in a terminal, which also works over SSH and
+//! in terminal multiplexers.
+
+
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::{cursor, event, queue, terminal};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, Stylize};
+
+use crate::{DISAPPEAR_BLINK_COUNT, FIELD_HEIGHT_BLOCKS, FIELD_WIDTH_BLOCKS, OPTS, TITLE_BLINK_FRAMES};
+use crate::clear_animation::ClearAnimation;
+use crate::frontend::{Frontend, Input};
+use crate::game::{Game, GameState};
+use crate::model::FieldBlock;
+use crate::render::Rgba;
+use crate::symbols::{SYMBOLS, contrasting_color};
+use crate::theme::Theme;
+
+
+/// Each block is two characters wide so that it looks roughly square.
+const BLOCK: &str = "\u{2588}\u{2588}";
+const SHADOW_BLOCK: &str = "\u{2591}\u{2591}";
+const EMPTY_BLOCK: &str = "  ";
+
+
+/// What is shown in one place of the field.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum Cell {
+    Empty,
+    Block(u8),
+    Highlight,
+    Shadow(u8),
+}
+impl Cell {
+    /// Draws the cell in the colors of the theme's palette; with symbols, the blocks show the
+    /// symbol of their color instead of being solid.
+    fn to_styled_string(self, theme: &Theme) -> String {
+        match self {
+            Self::Empty => EMPTY_BLOCK.to_owned(),
+            Self::Block(color_index) => {
+                let color = theme.palette[usize::from(color_index)];
+                if theme.symbols {
+                    let symbol = format!("{} ", SYMBOLS[usize::from(color_index)].glyph());
+                    symbol.with(terminal_color(contrasting_color(color))).on(terminal_color(color)).to_string()
+                } else {
+                    BLOCK.with(terminal_color(color)).to_string()
+                }
+            },
+            Self::Highlight => BLOCK.with(Color::White).to_string(),
+            Self::Shadow(color_index) => {
+                // shadows and ghosts are darker than the blocks
+                let color = theme.palette[usize::from(color_index)];
+                let dark_color = Rgba::rgb(color.r / 2, color.g / 2, color.b / 2);
+                SHADOW_BLOCK.with(terminal_color(dark_color)).to_string()
+            },
+        }
+    }
+}
+
+
+fn terminal_color(color: Rgba) -> Color {
+    Color::Rgb {
+        r: color.r,
+        g: color.g,
+        b: color.b,
+    }
+}
+
+
+/// Returns the lines of text showing the field, the score and the state of the game.
+fn frame_lines(game: &Game, theme: &Theme, shadows: bool, clear_animation: ClearAnimation, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>) -> Vec<String> {
+    let field = &game.field;
+    let width: usize = FIELD_WIDTH_BLOCKS.try_into().unwrap();
+    let mut cells = vec![Cell::Empty; field.blocks().len()];
+
+    let shadow_blocks = if shadows { crate::shadow_blocks(field) } else { Vec::new() };
+    for &(x, y, color_index) in shadow_blocks.iter().chain(ghost_blocks) {
+        let index: usize = (y * FIELD_WIDTH_BLOCKS + x).try_into().unwrap();
+        cells[index] = Cell::Shadow(color_index);
+    }
+    for (cell, field_block) in cells.iter_mut().zip(field.blocks()) {
+        if let FieldBlock::Block(block) = field_block {
+            *cell = match block.state.disappearing_counter() {
+                None => Cell::Block(block.color_index),
+                Some(counter) => match clear_animation {
+                    // blink like the window does
+                    ClearAnimation::Blink => if (counter & (1 << 3)) == 0 {
+                        Cell::Block(block.color_index)
+                    } else {
+                        Cell::Highlight
+                    },
+                    ClearAnimation::Highlight => Cell::Highlight,
+                    // text cannot fade or shrink gradually; dim the block halfway through instead
+                    ClearAnimation::Fade|ClearAnimation::Shrink|ClearAnimation::Dissolve => if counter > DISAPPEAR_BLINK_COUNT / 2 {
+                        Cell::Block(block.color_index)
+                    } else {
+                        Cell::Shadow(block.color_index)
+                    },
+                },
+            };
+        }
+    }
+
+    let mut panel = vec![
+        "COLUMNS".bold().to_string(),
+        String::new(),
+        format!("Score {:>6}", game.score),
+    ];
+    if let Some(remaining) = hints_remaining {
+        panel.push(format!("Hints {:>6}", remaining));
+    }
+    panel.push(String::new());
+    match game.state {
+        GameState::Play => {},
+        GameState::Pause => panel.push("PAUSED".bold().to_string()),
+        GameState::Over => panel.push("GAME OVER".bold().to_string()),
+        GameState::Title => {
+            panel.push("DEMO".bold().to_string());
+            if (game.frame_count / TITLE_BLINK_FRAMES) % 2 == 0 {
+                panel.push("Press any key to play".to_owned());
+            }
+        },
+    }
+    while panel.len() < 10 {
+        panel.push(String::new());
+    }
+    panel.push("\u{2190} \u{2192}  move".to_owned());
+    panel.push("\u{2191}    rotate".to_owned());
+    panel.push("\u{2193}    drop".to_owned());
+    panel.push("F2   new game".to_owned());
+    panel.push("F3   pause".to_owned());
+    panel.push("H    hint".to_owned());
+    panel.push("F12  screenshot".to_owned());
+    panel.push("Esc  quit".to_owned());
+
+    let horizontal_border = "\u{2500}".repeat(width * EMPTY_BLOCK.len());
+    let mut lines = Vec::with_capacity(cells.len() / width + 2);
+    lines.push(format!("\u{250C}{}\u{2510}", horizontal_border));
+    for row in cells.chunks(width) {
+        let mut line = String::from("\u{2502}");
+        for cell in row {
+            line.push_str(&cell.to_styled_string(theme));
+        }
+        line.push('\u{2502}');
+        lines.push(line);
+    }
+    lines.push(format!("\u{2514}{}\u{2518}", horizontal_border));
+
+    // put the panel to the right of the field, starting below the top border
+    for (line, panel_line) in lines.iter_mut().skip(1).zip(panel) {
+        if !panel_line.is_empty() {
+            line.push_str("  ");
+            line.push_str(&panel_line);
+        }
+    }
+    lines
+}
+
+
+fn key_to_input(key: KeyEvent) -> Input {
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+        // raw mode swallows the interrupt signal
+        return Input::Quit;
+    }
+    match key.code {
+        KeyCode::Esc => Input::Quit,
+        KeyCode::Left => Input::Left,
+        KeyCode::Right => Input::Right,
+        KeyCode::Up => Input::Rotate,
+        KeyCode::Down => Input::Drop,
+        KeyCode::F(2) => Input::NewGame,
+        KeyCode::F(3) => Input::TogglePause,
+        KeyCode::F(12) => Input::Screenshot,
+        KeyCode::Char(c) => match c.to_ascii_lowercase() {
+            'a'|'j' => Input::Left,
+            'd'|'l' => Input::Right,
+            'w'|'i' => Input::Rotate,
+            's'|'k' => Input::Drop,
+            'h' => Input::ToggleHint,
+            _ => Input::OtherKey,
+        },
+        _ => Input::OtherKey,
+    }
+}
+
+
+/// Shows the game in the terminal, which is switched to raw mode for as long as this exists.
+pub(crate) struct TuiFrontend {
+    stdout: Stdout,
+
+    /// The lines that are currently shown, so that only changed lines are written out.
+    shown_lines: Vec<String>,
+}
+impl TuiFrontend {
+    pub fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        queue!(stdout, terminal::EnterAlternateScreen, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+        stdout.flush()?;
+        Ok(Self {
+            stdout,
+            shown_lines: Vec::new(),
+        })
+    }
+}
+impl Frontend for TuiFrontend {
+    fn poll_inputs(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        while event::poll(Duration::ZERO).unwrap() {
+            match event::read().unwrap() {
+                Event::Key(key) if key.kind != KeyEventKind::Release => inputs.push(key_to_input(key)),
+                Event::Resize(_, _) => {
+                    // the terminal may have lost what was shown
+                    queue!(self.stdout, terminal::Clear(terminal::ClearType::All)).unwrap();
+                    self.shown_lines.clear();
+                    inputs.push(Input::Redraw);
+                },
+                _ => {},
+            }
+        }
+        inputs
+    }
+
+    fn draw(&mut self, game: &Game, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>) {
+        let opts = OPTS.get().expect("OPTS not set?!");
+        let lines = frame_lines(game, crate::theme::current(), opts.shadows, opts.clear_animation(), ghost_blocks, hints_remaining);
+        assert_eq!(lines.len(), usize::try_from(FIELD_HEIGHT_BLOCKS).unwrap() + 2);
+
+        for (i, line) in lines.iter().enumerate() {
+            if self.shown_lines.get(i) == Some(line) {
+                continue;
+            }
+            queue!(
+                self.stdout,
+                cursor::MoveTo(0, i.try_into().unwrap()),
+                Print(line),
+                terminal::Clear(terminal::ClearType::UntilNewLine),
+            ).unwrap();
+        }
+        self.stdout.flush().unwrap();
+        self.shown_lines = lines;
+    }
+}
+impl Drop for TuiFrontend {
+    fn drop(&mut self) {
+        // restore the terminal as well as possible; there is nobody left to report errors to
+        let _ = queue!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = self.stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_lines, key_to_input};
+    use crate::clear_animation::ClearAnimation;
+    use crate::frontend::Input;
+    use crate::game::{Game, GameState};
+    use crate::model::{Block, BlockState, FieldBlock};
+    use crate::theme::{Palette, Theme};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn test_frame_lines() {
+        let mut game = Game::new(0);
+        game.state = GameState::Over;
+        let lines = frame_lines(&game, &Theme::default(), false, ClearAnimation::Blink, &[], Some(3));
+        assert_eq!(lines.len(), 20);
+        assert!(lines[0].starts_with("\u{250C}\u{2500}"));
+        assert!(lines[1].starts_with("\u{2502}            \u{2502}"));
+        assert!(lines.iter().any(|line| line.contains("GAME OVER")));
+        assert!(lines.iter().any(|line| line.contains("Hints      3")));
+
+        // blocks are drawn in the palette, marked with their symbols
+        *game.field.block_by_coord_mut(0, 17) = FieldBlock::Block(Block {
+            color_index: 0,
+            state: BlockState::Stationary,
+        });
+        let theme = Theme {
+            palette: Palette::OkabeIto.colors(),
+            symbols: true,
+            ..Theme::default()
+        };
+        let lines = frame_lines(&game, &theme, false, ClearAnimation::Blink, &[], None);
+        assert!(lines[18].contains("\u{25CF} "));
+        assert!(lines[18].contains("48;2;213;94;0"));
+        let lines = frame_lines(&game, &Theme::default(), false, ClearAnimation::Blink, &[], None);
+        assert!(lines[18].contains("\u{2588}\u{2588}"));
+
+        assert_eq!(key_to_input(KeyEvent::new(KeyCode::Char('J'), KeyModifiers::SHIFT)), Input::Left);
+        assert_eq!(key_to_input(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Input::Quit);
+        assert_eq!(key_to_input(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE)), Input::OtherKey);
+    }
+}
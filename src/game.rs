@@ -0,0 +1,339 @@
+use rand::SeedableRng;
+use rand::distributions::Uniform;
+use rand::rngs::StdRng;
+
+use crate::{
+    BLOCK_COLOR_COUNT, BLOCK_HEIGHT_PX, DEFAULT_BLOCK_FALL_LIMIT, FIELD_HEIGHT_BLOCKS,
+    NEW_BLOCK_COLUMN, SCORE_SPEEDUP_DIVISOR,
+};
+use crate::ai::BestMove;
+use crate::model::{BlockState, Field, FieldBlock};
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum GameState {
+    #[default] Play,
+    Pause,
+    Over,
+
+    /// The title screen, during which the AI plays a demo game until a key is pressed.
+    Title,
+}
+impl GameState {
+    /// Returns whether the game advances in this state.
+    pub fn is_running(&self) -> bool {
+        matches!(self, Self::Play | Self::Title)
+    }
+}
+
+
+/// Something noteworthy that happened during a tick of the game.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum GameEvent {
+    /// A new piece of descending blocks has appeared at the top of the field.
+    PieceSpawned,
+
+    /// There was no space for a new piece; the game is over.
+    GameOver,
+}
+
+
+/// A piece of descending blocks.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Piece {
+    /// The column containing the piece.
+    pub column: u32,
+
+    /// The color indexes of the piece's blocks from top to bottom.
+    pub colors: Vec<u8>,
+}
+impl Piece {
+    /// Returns the piece formed by the descending blocks on the field, if any.
+    pub fn from_field(field: &Field) -> Option<Self> {
+        // block_coords_with_predicate returns the blocks from bottom to top
+        let mut desc_blocks = field.block_coords_with_predicate(|b| b.is_descending());
+        desc_blocks.reverse();
+
+        let column = desc_blocks.first()?.0;
+        let colors = desc_blocks.iter()
+            .map(|&(x, y)| field.block_by_coord(x, y).color_index().unwrap())
+            .collect();
+        Some(Self {
+            column,
+            colors,
+        })
+    }
+
+    /// Returns how often this piece has to be rotated to obtain the colors of the other piece, or
+    /// `None` if this is not possible.
+    pub fn rotations_to(&self, other: &Piece) -> Option<usize> {
+        if self.colors.len() != other.colors.len() {
+            return None;
+        }
+
+        // each rotation moves every color one block down and the bottommost color to the top
+        let mut rotated = self.colors.clone();
+        for rotate_count in 0..self.colors.len().max(1) {
+            if rotated == other.colors {
+                return Some(rotate_count);
+            }
+            rotated.rotate_right(1);
+        }
+        None
+    }
+}
+
+
+/// Creates the random number generator for the blocks from the given seed.
+pub(crate) fn rng_from_seed(seed: u128) -> StdRng {
+    let mut rng_seed_bytes = [0u8; 32];
+    rng_seed_bytes[0..128/8].copy_from_slice(&seed.to_be_bytes());
+    StdRng::from_seed(rng_seed_bytes)
+}
+
+
+/// Creates the random number generator for the AI from the given seed.
+///
+/// The AI gets its own generator so that it doesn't influence the blocks.
+pub(crate) fn ai_rng_from_seed(seed: u128) -> StdRng {
+    let mut rng_seed_bytes = [0u8; 32];
+    rng_seed_bytes[0..128/8].copy_from_slice(&seed.to_be_bytes());
+    rng_seed_bytes[128/8..].copy_from_slice(&seed.to_le_bytes());
+    StdRng::from_seed(rng_seed_bytes)
+}
+
+
+fn handle_descending_blocks(field: &mut Field, descending_block_coords: &[(u32, u32)]) {
+    for &(x, y) in descending_block_coords {
+        let this_block = field.block_by_coord(x, y);
+
+        if field.block_at_coord_hit_bottom_or_stationary_block(x, y) {
+            // we are no longer descending
+            field.block_by_coord_mut(x, y)
+                .as_block_mut().unwrap()
+                .state = BlockState::Stationary;
+        } else {
+            *field.block_by_coord_mut(x, y + 1) = this_block.clone();
+            *field.block_by_coord_mut(x, y) = FieldBlock::Background;
+        }
+    }
+}
+
+
+/// The rules of the game, independent of any input or output.
+pub(crate) struct Game {
+    pub field: Field,
+    pub state: GameState,
+    pub score: u64,
+    pub color_stats: [u32; BLOCK_COLOR_COUNT],
+    pub block_fall_counter: u64,
+    pub block_fall_limit: u64,
+
+    /// The piece as it appeared at the top of the field.
+    pub spawned_piece: Option<Piece>,
+
+    /// The current piece as it was placed, once it has stopped descending; `None` while it is
+    /// still descending.
+    pub placed_piece: Option<Piece>,
+
+    /// The number of frames the game has advanced so far.
+    pub frame_count: u64,
+
+    /// Whether this is a demo game played on the title screen.
+    pub is_demo: bool,
+
+    rng: StdRng,
+    color_distribution: Uniform<u8>,
+}
+impl Game {
+    pub fn new(seed: u128) -> Self {
+        Self {
+            field: Field::new(),
+            state: GameState::Play,
+            score: 0,
+            color_stats: [0; BLOCK_COLOR_COUNT],
+            block_fall_counter: 0,
+            block_fall_limit: DEFAULT_BLOCK_FALL_LIMIT,
+            spawned_piece: None,
+            placed_piece: None,
+            frame_count: 0,
+            is_demo: false,
+            rng: rng_from_seed(seed),
+            color_distribution: Uniform::new(0, u8::try_from(BLOCK_COLOR_COUNT).unwrap()),
+        }
+    }
+
+    /// Starts a new game, continuing with the same random number generator.
+    pub fn restart(&mut self) {
+        self.field = Field::new();
+        for color_stat in &mut self.color_stats {
+            *color_stat = 0;
+        }
+        self.score = 0;
+        self.block_fall_limit = DEFAULT_BLOCK_FALL_LIMIT;
+        self.spawned_piece = None;
+        self.placed_piece = None;
+        self.state = GameState::Play;
+    }
+
+    /// Creates a demo game for the title screen.
+    pub fn new_demo(seed: u128) -> Self {
+        let mut game = Self::new(seed);
+        game.state = GameState::Title;
+        game.is_demo = true;
+        game
+    }
+
+    /// Pauses the game if it is running and continues it if it is paused.
+    pub fn toggle_pause(&mut self) {
+        self.state = match self.state {
+            GameState::Over => GameState::Over,
+            GameState::Title => GameState::Title,
+            GameState::Play => GameState::Pause,
+            GameState::Pause => GameState::Play,
+        };
+    }
+
+    /// Moves and rotates the descending blocks as described by the move.
+    pub fn apply_move(&mut self, best_move: &BestMove) {
+        let desc_blocks = self.field.block_coords_with_predicate(|b| b.is_descending());
+        let current_column = desc_blocks.first()
+            .map(|&(x, _y)| x)
+            .unwrap_or(NEW_BLOCK_COLUMN);
+
+        let column_delta = i32::try_from(best_move.column).unwrap() - i32::try_from(current_column).unwrap();
+        if column_delta > 0 {
+            for _ in 0..column_delta {
+                self.field.move_descending_blocks_right();
+            }
+        } else if column_delta < 0 {
+            for _ in 0..(-column_delta) {
+                self.field.move_descending_blocks_left();
+            }
+        }
+        for _ in 0..best_move.rotate_count {
+            self.field.rotate_descending_blocks();
+        }
+    }
+
+    /// Stops the descending blocks from being controlled and lets them drop down immediately.
+    pub fn drop_piece(&mut self) {
+        if let Some(piece) = Piece::from_field(&self.field) {
+            self.placed_piece = Some(piece);
+        }
+        self.field.hand_descending_blocks_to_gravity();
+    }
+
+    /// Advances the game by one frame.
+    pub fn tick(&mut self) -> Option<GameEvent> {
+        if !self.state.is_running() {
+            return None;
+        }
+        self.frame_count += 1;
+
+        let mut event = None;
+        let disappearing_block_coords = self.field
+            .block_coords_with_predicate(|bs| bs.is_disappearing());
+        if !disappearing_block_coords.is_empty() {
+            // count down
+            self.field.reduce_disappearing_blocks();
+
+            // continue immediately
+            self.block_fall_counter = self.block_fall_limit;
+        } else {
+            let gravity_block_coords = self.field
+                .block_coords_with_predicate(|bs| bs.is_pulled_by_gravity());
+            if !gravity_block_coords.is_empty() {
+                self.field.descend_gravity_blocks();
+
+                // continue immediately
+                self.block_fall_counter = self.block_fall_limit;
+            } else {
+                if self.block_fall_counter == self.block_fall_limit {
+                    event = self.handle_block_fall();
+                }
+                self.block_fall_counter += 1;
+            }
+        }
+
+        self.regulate_color_stats();
+        event
+    }
+
+    fn handle_block_fall(&mut self) -> Option<GameEvent> {
+        // handle descending blocks
+        self.block_fall_counter = 0;
+
+        let descending_block_coords = self.field
+            .block_coords_with_predicate(|bs| bs.is_descending());
+        let piece = Piece::from_field(&self.field);
+        handle_descending_blocks(&mut self.field, &descending_block_coords);
+        if Piece::from_field(&self.field).is_none() {
+            // the blocks have settled
+            if let Some(p) = piece {
+                self.placed_piece = Some(p);
+            }
+        }
+
+        if !descending_block_coords.is_empty() {
+            return None;
+        }
+
+        // no more descending blocks
+
+        // any sequences?
+        let old_score_divided = self.score / SCORE_SPEEDUP_DIVISOR;
+        let sequences_found = self.field.disappear_scoring_sequences(&mut self.score);
+        if sequences_found {
+            if self.block_fall_limit > 1 {
+                let new_score_divided = self.score / SCORE_SPEEDUP_DIVISOR;
+                if new_score_divided > old_score_divided {
+                    // increase speed by lowering the limit
+                    self.block_fall_limit -= 1;
+                }
+            }
+
+            // continue immediately
+            self.block_fall_counter = self.block_fall_limit - 1;
+            None
+        } else if self.field.make_new_descending_block(&self.color_distribution, &mut self.rng, &mut self.color_stats) {
+            self.spawned_piece = Piece::from_field(&self.field);
+            self.placed_piece = None;
+            Some(GameEvent::PieceSpawned)
+        } else {
+            // GAME OVER
+            self.state = GameState::Over;
+            Some(GameEvent::GameOver)
+        }
+    }
+
+    /// Keeps the color statistics from growing beyond the height of the field.
+    fn regulate_color_stats(&mut self) {
+        if self.color_stats.iter().any(|stat| *stat >= (FIELD_HEIGHT_BLOCKS - 1) * BLOCK_HEIGHT_PX) {
+            let delta = self.color_stats.iter().copied().min().unwrap();
+            if delta == 0 {
+                // pathological case where some color simply didn't appear often enough
+                // just reset all the stats
+                for color_stat in &mut self.color_stats {
+                    *color_stat = 0;
+                }
+            } else {
+                for color_stat in &mut self.color_stats {
+                    *color_stat -= delta;
+                }
+            }
+        }
+    }
+
+    /// Advances the game until a new piece has appeared or the game is over.
+    ///
+    /// Returns the event that ended the advance, or `None` if the game is not running.
+    pub fn run_until_next_piece(&mut self) -> Option<GameEvent> {
+        while self.state.is_running() {
+            if let Some(event) = self.tick() {
+                return Some(event);
+            }
+        }
+        None
+    }
+}
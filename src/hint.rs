@@ -0,0 +1,181 @@
+use crate::FIELD_HEIGHT_BLOCKS;
+use crate::ai::BestMove;
+use crate::game::{Game, Piece};
+
+
+/// Where and in which orientation the current piece should be placed.
+struct Hint {
+    column: u32,
+
+    /// The colors of the piece from top to bottom once it has been rotated as recommended.
+    colors: Vec<u8>,
+}
+
+
+/// Keeps track of the hint for the current piece and how many hints are left.
+pub(crate) struct Hints {
+    visible: bool,
+    limit: Option<u32>,
+    remaining: Option<u32>,
+    current: Option<Hint>,
+    current_counted: bool,
+
+    /// Whether a hint has been calculated for the current piece, even if no move was found.
+    current_requested: bool,
+}
+impl Hints {
+    /// Creates the hint state; `limit` is the number of pieces for which hints may be shown per
+    /// game, or `None` for no limit.
+    pub fn new(limit: Option<u32>) -> Self {
+        Self {
+            visible: false,
+            limit,
+            remaining: limit,
+            current: None,
+            current_counted: false,
+            current_requested: false,
+        }
+    }
+
+    /// Shows the hints if they are hidden and hides them if they are shown.
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Refills the hints for a new game.
+    pub fn restart(&mut self) {
+        self.remaining = self.limit;
+        self.new_piece();
+    }
+
+    /// Forgets the hint for the previous piece.
+    pub fn new_piece(&mut self) {
+        self.current = None;
+        self.current_counted = false;
+        self.current_requested = false;
+    }
+
+    /// Returns how many hints are left, or `None` if they are not limited.
+    pub fn remaining(&self) -> Option<u32> {
+        self.remaining
+    }
+
+    /// Returns whether a hint should be calculated for the current piece.
+    pub fn wants_hint(&self) -> bool {
+        self.visible
+            && !self.current_requested
+            && (self.current_counted || self.remaining != Some(0))
+    }
+
+    /// Sets the hint for the current piece, using up one of the remaining hints if necessary. If no
+    /// move was found, no hint is shown and none is calculated again until the next piece.
+    ///
+    /// The rotation of the move is relative to the piece as it currently is on the field.
+    pub fn set_hint(&mut self, game: &Game, best_move: Option<BestMove>) {
+        self.current_requested = true;
+        let best_move = match best_move {
+            Some(bm) => bm,
+            None => return,
+        };
+        let piece = match Piece::from_field(&game.field) {
+            Some(p) => p,
+            None => return,
+        };
+
+        // each rotation moves every color one block down and the bottommost color to the top
+        let mut colors = piece.colors;
+        if !colors.is_empty() {
+            let rotate_count = best_move.rotate_count % colors.len();
+            colors.rotate_right(rotate_count);
+        }
+
+        self.current = Some(Hint {
+            column: best_move.column,
+            colors,
+        });
+        if !self.current_counted {
+            if let Some(remaining) = self.remaining.as_mut() {
+                *remaining = remaining.saturating_sub(1);
+            }
+            self.current_counted = true;
+        }
+    }
+
+    /// Returns the blocks of the ghost piece as (x, y, color index), or an empty vector if no hint
+    /// is shown.
+    pub fn ghost_blocks(&self, game: &Game) -> Vec<(u32, u32, u8)> {
+        if !self.visible {
+            return Vec::new();
+        }
+        let hint = match &self.current {
+            Some(h) => h,
+            None => return Vec::new(),
+        };
+        if Piece::from_field(&game.field).is_none() {
+            // the piece has already been placed
+            return Vec::new();
+        }
+
+        // find the top of the stack in the target column, ignoring the descending blocks
+        let mut stack_top = FIELD_HEIGHT_BLOCKS;
+        while stack_top > 0 {
+            let block = game.field.block_by_coord(hint.column, stack_top - 1);
+            let is_stack = block.as_block()
+                .map(|b| !b.state.is_descending())
+                .unwrap_or(false);
+            if !is_stack {
+                break;
+            }
+            stack_top -= 1;
+        }
+
+        let piece_height = u32::try_from(hint.colors.len()).unwrap();
+        if stack_top < piece_height {
+            // doesn't fit
+            return Vec::new();
+        }
+        let top_y = stack_top - piece_height;
+        hint.colors.iter()
+            .enumerate()
+            .map(|(i, &color)| (hint.column, top_y + u32::try_from(i).unwrap(), color))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Hints;
+    use crate::ai::BestMove;
+    use crate::game::Game;
+
+    #[test]
+    fn test_hints() {
+        let mut game = Game::new(0);
+        game.run_until_next_piece();
+        let mut hints = Hints::new(Some(1));
+        assert!(!hints.wants_hint());
+        hints.toggle();
+        assert!(hints.wants_hint());
+
+        // a piece without a move isn't asked about again and doesn't use up a hint
+        hints.set_hint(&game, None);
+        assert!(!hints.wants_hint());
+        assert!(hints.ghost_blocks(&game).is_empty());
+        assert_eq!(hints.remaining(), Some(1));
+
+        hints.new_piece();
+        assert!(hints.wants_hint());
+        hints.set_hint(&game, Some(BestMove { column: 0, rotate_count: 0 }));
+        assert!(!hints.wants_hint());
+        assert_eq!(hints.remaining(), Some(0));
+        let ghost_blocks = hints.ghost_blocks(&game);
+        assert_eq!(ghost_blocks.len(), 3);
+        assert!(ghost_blocks.iter().all(|&(x, y, _color)| x == 0 && y >= 15));
+
+        hints.new_piece();
+        assert!(!hints.wants_hint());
+        hints.restart();
+        assert!(hints.wants_hint());
+    }
+}
@@ -1,27 +1,64 @@
 mod ai;
+mod analysis;
+mod autoplay;
+mod bot;
+mod clear_animation;
+mod config;
+mod effects;
+mod env;
+mod export;
+mod frontend;
+mod game;
+mod hint;
 mod model;
+mod motion;
+mod pixel_font;
+mod puzzle;
+mod record;
+mod render;
+mod screenshot;
 mod seg_display;
+mod symbols;
+mod theme;
+mod tournament;
 
 
 use std::collections::BTreeSet;
+use std::io::{self, IsTerminal};
 use std::iter::once;
+use std::path::PathBuf;
+use std::sync::Mutex;
 use std::thread::sleep;
 use std::time::Duration;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use once_cell::sync::OnceCell;
-use rand::{SeedableRng, thread_rng, Rng};
-use rand::distributions::Uniform;
+use rand::{thread_rng, Rng};
 use rand::rngs::StdRng;
-use sdl2::event::{Event, WindowEvent};
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::{Color, PixelFormatEnum};
-use sdl2::rect::Rect;
-use sdl2::render::{BlendMode, Canvas, Texture, TextureAccess, TextureCreator};
-use sdl2::video::Window;
-
-use crate::model::{BlockState, Field, FieldBlock};
+
+use crate::ai::{AiStrategy, BestMove};
+use crate::ai::adaptive::DangerThresholds;
+use crate::ai::explain::{AiOutput, Explanation};
+use crate::autoplay::AnimatedMove;
+use crate::bot::Bot;
+use crate::clear_animation::ClearAnimation;
+use crate::config::Config;
+use crate::effects::{Effect, Effects};
+use crate::frontend::{Frontend, Input};
+#[cfg(feature = "sdl")]
+use crate::frontend::sdl::SdlFrontend;
+use crate::frontend::tui::TuiFrontend;
+use crate::game::{Game, GameEvent, GameState, Piece};
+use crate::hint::Hints;
+use crate::model::{Field, FieldBlock};
+use crate::motion::Motion;
+use crate::pixel_font::{GLYPH_HEIGHT, draw_text, text_width};
+use crate::record::{Player, Recorder};
+use crate::render::{Renderer, Rgba};
+use crate::render::software::Image;
 use crate::seg_display::SegmentedDisplay;
+use crate::symbols::{SYMBOLS, contrasting_color, draw_symbol};
+use crate::theme::{Palette, ThemeSprites};
 
 
 const WINDOW_WIDTH: u32 = 800;
@@ -45,26 +82,30 @@ const COLOR_STATS_BAR_WIDTH: u32 = 8;
 const COLOR_STATS_BAR_SPACING: u32 = 2;
 const DEFAULT_BLOCK_FALL_LIMIT: u64 = 32;
 const SCORE_SPEEDUP_DIVISOR: u64 = 4;
+const HINT_COUNTER_OFFSET_LEFT_PX: i32 = 200;
+const TITLE_BLINK_FRAMES: u64 = 30;
+const TITLE_TEXT_SCALE: u32 = 4;
+const TITLE_TEXT_SPACING_PX: i32 = 24;
+const TITLE_TEXT_SHADOW_OFFSET_PX: i32 = 2;
+const DEMO_FRAMES_PER_ACTION: u32 = 8;
+const DEMO_DELAY_FRAMES: u32 = 20;
 
 const FIELD_BLOCK_COUNT: usize = (FIELD_WIDTH_BLOCKS * FIELD_HEIGHT_BLOCKS) as usize;
 const NEW_BLOCK_COLUMN: u32 = FIELD_WIDTH_BLOCKS / 2;
+const PIECE_LENGTH: usize = 3;
 
 
-const BLOCK_COLORS: [Color; BLOCK_COLOR_COUNT] = [
-    Color::RED, Color::GREEN, Color::BLUE,
-    Color::YELLOW, Color::CYAN, Color::MAGENTA,
+const BLOCK_COLORS: [Rgba; BLOCK_COLOR_COUNT] = [
+    Rgba::RED, Rgba::GREEN, Rgba::BLUE,
+    Rgba::YELLOW, Rgba::CYAN, Rgba::MAGENTA,
 ];
 
 
 static OPTS: OnceCell<Opts> = OnceCell::new();
 
+/// The messages printed while the terminal frontend is shown, which are held back until it is gone.
+static HELD_MESSAGES: Mutex<String> = Mutex::new(String::new());
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-enum GameState {
-    #[default] Play,
-    Pause,
-    Over,
-}
 
 #[derive(Clone, Debug, Eq, Hash, Ord, Parser, PartialEq, PartialOrd)]
 struct Opts {
@@ -88,23 +129,310 @@ struct Opts {
     #[arg(short = 'f', long)]
     pub fast_autoplay: bool,
 
+    /// If autoplay is on, moves or rotates the piece only once per this many frames instead of
+    /// placing it instantly.
+    #[arg(long, value_name = "FRAMES", default_value = "0")]
+    pub autoplay_frames_per_action: u32,
+
+    /// If autoplay is on, waits this many frames after a piece appears before moving it.
+    #[arg(long, value_name = "FRAMES", default_value = "0")]
+    pub autoplay_delay_frames: u32,
+
+    /// How the AI explains its moves if --ai is given.
+    #[arg(long, value_enum, default_value_t)]
+    pub ai_output: AiOutput,
+
+    /// The strategy used by the AI to pick the best move.
+    #[arg(long, value_enum, default_value_t)]
+    pub ai_strategy: AiStrategy,
+
+    /// How much time (in milliseconds) the AI may spend per move if the strategy supports it.
+    #[arg(long, default_value = "50")]
+    pub ai_time_budget_ms: u64,
+
+    /// With the adaptive strategy, the height of the stack in the column in which new pieces
+    /// appear at which the AI prioritizes survival over scoring.
+    #[arg(long, value_name = "BLOCKS", default_value_t = crate::ai::adaptive::DEFAULT_SPAWN_COLUMN_DANGER_HEIGHT)]
+    pub danger_spawn_column_height: u32,
+
+    /// With the adaptive strategy, the height of the highest tower at which the AI prioritizes
+    /// survival over scoring.
+    #[arg(long, value_name = "BLOCKS", default_value_t = crate::ai::adaptive::DEFAULT_TOWER_DANGER_HEIGHT)]
+    pub danger_tower_height: u32,
+
+    /// The number of threads the AI uses to evaluate moves; 0 uses one thread per CPU.
+    #[arg(long, default_value = "0")]
+    pub ai_threads: usize,
+
+    /// Starts an external bot with this command line and asks it for the best moves instead of the
+    /// built-in AI. The bot plays the game (as with --autoplay) unless --ai is given, in which case
+    /// its moves are only explained.
+    #[arg(long, value_name = "CMD")]
+    pub bot: Option<String>,
+
+    /// Records every placed piece along with the field and the outcome of the game into this file
+    /// as JSON lines.
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Limits the number of pieces per game for which hints (toggled with H) may be shown; at most
+    /// 99, as the counter has two digits.
+    #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(0..=99))]
+    pub hint_limit: Option<u32>,
+
+    /// Starts a game right away instead of showing the title screen with a demo game.
+    #[arg(long)]
+    pub skip_title: bool,
+
+    /// Plays in the terminal instead of a window (always the case if built without the sdl
+    /// feature).
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Only scales the window contents by whole multiples, which keeps the pixels sharp but may
+    /// leave wider black bars around the game.
+    #[arg(long)]
+    pub integer_scaling: bool,
+
+    /// Loads block graphics, a background and block colors from a theme directory (the files are
+    /// described at the top of `src/theme.rs`).
+    #[arg(long, value_name = "DIR")]
+    pub theme: Option<PathBuf>,
+
+    /// Replaces the block colors (including those of a theme) with another palette.
+    #[arg(long, value_enum, value_name = "NAME")]
+    pub palette: Option<Palette>,
+
+    /// Draws a distinct symbol onto the blocks of each color, which makes them easier to tell
+    /// apart for colorblind players.
+    #[arg(long)]
+    pub color_symbols: bool,
+
+    /// Picks how the blocks of completed sequences disappear.
+    #[arg(long, value_enum, value_name = "NAME")]
+    pub clear_animation: Option<ClearAnimation>,
+
+    /// Avoids rapidly flashing animations, for players who are sensitive to them (the blinking
+    /// clear animation becomes a fade); `--no-flash=false` allows them again.
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub no_flash: Option<bool>,
+
+    /// Adds effects to clears and cascades, given as a comma-separated list; `--effects` without
+    /// any turns them all off.
+    #[arg(long, value_enum, value_name = "LIST", value_delimiter = ',', num_args = 0..=1)]
+    pub effects: Option<Vec<Effect>>,
+
+    /// Reads the settings that are kept between runs from this file instead of the default one
+    /// (see `src/config.rs`).
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Saves `--clear-animation`, `--no-flash` and `--effects` into the configuration file so that they also
+    /// apply to later runs.
+    #[arg(long)]
+    pub save_config: bool,
+
     /// Feeds a specific seed to the random number generator.
     pub random_seed: Option<u128>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+impl Opts {
+    /// Returns how the blocks of completed sequences disappear, taking into account whether
+    /// flashing is allowed.
+    pub fn clear_animation(&self) -> ClearAnimation {
+        let animation = self.clear_animation.unwrap_or_default();
+        if animation.flashes() && self.no_flash == Some(true) {
+            ClearAnimation::Fade
+        } else {
+            animation
+        }
+    }
+
+    /// Returns the effects that are switched on.
+    pub fn effects(&self) -> Vec<Effect> {
+        self.effects.clone().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Subcommand)]
+enum Command {
+    /// Serves headless reinforcement-learning environments on standard input and output.
+    Env {
+        /// The number of environments that are reset and stepped together.
+        #[arg(short, long, default_value = "1")]
+        count: usize,
+
+        /// The number of threads used to step the environments; 0 uses one thread per CPU.
+        #[arg(long, default_value = "0")]
+        threads: usize,
+    },
+
+    /// Compares each move of the games in a record file with the AI's alternatives and reports the
+    /// mistakes.
+    Analyze {
+        /// The record file written using --record.
+        file: PathBuf,
+
+        /// Flags moves that score at least this many points fewer than the best move.
+        #[arg(long, default_value = "1")]
+        score_threshold: i64,
+
+        /// Flags moves that leave at least this many fewer extensible sequences than the best move
+        /// (if they score the same).
+        #[arg(long, default_value = "2")]
+        sequence_threshold: i64,
+
+        /// Flags moves that leave the highest tower at least this many blocks higher than the best
+        /// move (if they are otherwise equal).
+        #[arg(long, default_value = "2")]
+        height_threshold: i64,
+    },
+
+    /// Searches for the shortest sequence of placements that solves a puzzle.
+    Solve {
+        /// The puzzle file; the format is described at the top of src/puzzle.rs.
+        file: PathBuf,
+    },
+
+    /// Generates puzzles that are guaranteed to be solvable.
+    Generate {
+        /// The directory into which the puzzle files are written.
+        dir: PathBuf,
+
+        /// The seed from which the puzzles are generated; random if not given.
+        #[arg(long)]
+        seed: Option<u128>,
+
+        /// The number of puzzles to generate.
+        #[arg(short, long, default_value = "10")]
+        count: usize,
+
+        /// The largest number of pieces in a puzzle.
+        #[arg(long, default_value = "4")]
+        max_pieces: usize,
+
+        /// The height of the highest tower in the start field.
+        #[arg(long, default_value = "5")]
+        max_height: u32,
+
+        /// The number of different colors in a puzzle.
+        #[arg(long, default_value = "4")]
+        colors: usize,
+
+        /// Discards puzzles that are easier than this.
+        #[arg(long, default_value = "1")]
+        min_difficulty: u32,
+
+        /// How many random candidates are tried for each puzzle before giving up.
+        #[arg(long, default_value = "10000")]
+        max_attempts: u32,
+    },
+
+    /// Plays headless games with several AI configurations on the same seeds and compares their
+    /// scores.
+    Tournament {
+        /// The contestants: greedy, mcts, adaptive, weights:FILE or bot:CMD.
+        #[arg(required = true, num_args = 2..)]
+        contestants: Vec<String>,
+
+        /// How many games each contestant plays.
+        #[arg(short, long, default_value = "20")]
+        games: usize,
+
+        /// The seed of the first game; the following games use the subsequent seeds.
+        #[arg(long, default_value = "0")]
+        seed: u128,
+
+        /// Ends each game once this many pieces have been placed.
+        #[arg(long, default_value = "500")]
+        max_pieces: u64,
+
+        /// How much time (in milliseconds) the MCTS strategy may spend per move.
+        #[arg(long, default_value = "50")]
+        time_budget_ms: u64,
+
+        /// The number of games played at the same time; 0 uses one thread per CPU.
+        #[arg(long, default_value = "0")]
+        threads: usize,
+    },
+
+    /// Draws a game state into an image file the same way as the window does, without needing a
+    /// display.
+    Render {
+        /// The image file; it is written as a bitmap if its name ends in .bmp and as a PNG
+        /// otherwise.
+        output: PathBuf,
+
+        /// Draws the state of a game in a record file (written using --record) as the piece of
+        /// --move appeared, instead of a new game.
+        #[arg(long, value_name = "FILE", conflicts_with = "puzzle")]
+        from_record: Option<PathBuf>,
+
+        /// Draws the start field of a puzzle instead of a new game.
+        #[arg(long, value_name = "FILE")]
+        puzzle: Option<PathBuf>,
+
+        /// With --from-record, the index of the game in the record file.
+        #[arg(long, default_value = "0")]
+        game: u64,
+
+        /// With --from-record, the index of the move within the game; otherwise, the number of
+        /// pieces the AI places in the new game before it is drawn.
+        #[arg(long = "move", default_value = "0")]
+        move_index: u64,
+
+        /// The seed of the new game.
+        #[arg(long, default_value = "0")]
+        seed: u128,
+    },
+
+    /// Replays a game from a record file and saves it as an animated GIF or as numbered PNG files.
+    Export {
+        /// The record file written using --record.
+        record: PathBuf,
+
+        /// The GIF file if the name ends in .gif, otherwise the directory into which the frames
+        /// are written as PNG files.
+        output: PathBuf,
+
+        /// The index of the game in the record file.
+        #[arg(long, default_value = "0")]
+        game: u64,
+
+        /// The index of the first move that is shown.
+        #[arg(long, default_value = "0")]
+        first_move: u64,
+
+        /// The index of the last move that is shown; by default, the game is shown until its end.
+        #[arg(long)]
+        last_move: Option<u64>,
+
+        /// Leaves out this many frames after each frame that is written; the game runs at 60
+        /// frames per second.
+        #[arg(long, value_name = "FRAMES", default_value = "1")]
+        frame_skip: u64,
+
+        /// The size of the frames in percent of the size of the window.
+        #[arg(long, value_name = "PERCENT", default_value = "100")]
+        scale: u32,
+    },
 }
 
 const fn mul_div(val: u8, numerator: u8, denominator: u8) -> u8 {
     ((val as u16) * (numerator as u16) / (denominator as u16)) as u8
 }
-const fn mul_div_rgb(color: Color, numerator: u8, denominator: u8) -> Color {
-    Color::RGB(
+const fn mul_div_rgb(color: Rgba, numerator: u8, denominator: u8) -> Rgba {
+    Rgba::rgb(
         mul_div(color.r, numerator, denominator),
         mul_div(color.g, numerator, denominator),
         mul_div(color.b, numerator, denominator),
     )
 }
-const fn brighten_rgb(color: Color, divisor: u8) -> Color {
+const fn brighten_rgb(color: Rgba, divisor: u8) -> Rgba {
     let base = mul_div(255, divisor-1, divisor);
-    Color::RGB(
+    Rgba::rgb(
         base + mul_div(color.r, 1, divisor),
         base + mul_div(color.g, 1, divisor),
         base + mul_div(color.b, 1, divisor),
@@ -112,94 +440,120 @@ const fn brighten_rgb(color: Color, divisor: u8) -> Color {
 }
 
 
-fn draw(
-    canvas: &mut Canvas<Window>,
-    field: &Field,
-    game_state: GameState,
-    score: u64,
-    color_stats: &[u32; BLOCK_COLOR_COUNT],
-    block_textures: &[Texture],
+/// Returns the coordinates and colors at which the descending blocks would land if they were
+/// dropped.
+fn shadow_blocks(field: &Field) -> Vec<(u32, u32, u8)> {
+    // find the deepest descending block
+    let descending_blocks = field.block_coords_with_predicate(|b| b.is_descending());
+    if descending_blocks.is_empty() {
+        return Vec::new();
+    }
+    let (deepest_x, deepest_y) = descending_blocks.iter()
+        .map(|xy| *xy)
+        .max_by_key(|(_, y)| *y)
+        .unwrap();
+
+    // see how deep we can drop it
+    let mut test_y = deepest_y;
+    while !field.block_at_coord_hit_bottom_or_stationary_block(deepest_x, test_y) {
+        test_y += 1;
+    }
+
+    let y_offset = test_y - deepest_y;
+    descending_blocks.iter()
+        .map(|&(x, y)| (x, y + y_offset, field.block_by_coord(x, y).color_index().unwrap()))
+        .collect()
+}
+
+
+fn draw<R: Renderer>(
+    renderer: &mut R,
+    game: &Game,
+    motion: &Motion,
+    effects: &Effects,
+    ghost_blocks: &[(u32, u32, u8)],
+    hints_remaining: Option<u32>,
+    sprites: &ThemeSprites<R::Sprite>,
 ) {
-    canvas.set_draw_color((0, 0, 0));
-    canvas.clear();
+    let field = &game.field;
+    let block_textures = &sprites.blocks;
 
-    canvas.set_draw_color((0xC0, 0xC0, 0xC0));
-    canvas.draw_rect(Rect::new(
+    renderer.clear(Rgba::BLACK);
+    if let Some(background) = &sprites.background {
+        renderer.blit_sprite(background, 0, 0, WINDOW_WIDTH, WINDOW_HEIGHT);
+    }
+
+    renderer.draw_rect(
         FIELD_OFFSET_LEFT_PX - FIELD_FRAME_OFFSET_PX,
         FIELD_OFFSET_TOP_PX - FIELD_FRAME_OFFSET_PX,
         BLOCK_WIDTH_PX * FIELD_WIDTH_BLOCKS + u32::try_from(2*FIELD_FRAME_OFFSET_PX).unwrap(),
         BLOCK_HEIGHT_PX * FIELD_HEIGHT_BLOCKS + u32::try_from(2*FIELD_FRAME_OFFSET_PX).unwrap(),
-    )).unwrap();
+        Rgba::rgb(0xC0, 0xC0, 0xC0),
+    );
 
     let opts = OPTS.get().expect("OPTS not set?!");
     if opts.shadows {
-        // find the deepest descending block
-        let descending_blocks = field.block_coords_with_predicate(|b| b.is_descending());
-        if descending_blocks.len() > 0 {
-            let (deepest_x, deepest_y) = descending_blocks.iter()
-                .map(|xy| *xy)
-                .max_by_key(|(_, y)| *y)
-                .unwrap();
-
-            // see how deep we can drop it
-            let mut test_y = deepest_y;
-            while !field.block_at_coord_hit_bottom_or_stationary_block(deepest_x, test_y) {
-                test_y += 1;
-            }
-
-            let y_offset = test_y - deepest_y;
-
-            // draw the shadow there
-            for &(x, y) in &descending_blocks {
-                let shadow_color_index =
-                    BLOCK_COLOR_COUNT
-                    + 1
-                    + usize::from(field.block_by_coord(x, y).color_index().unwrap())
-                ;
-
-                let actual_x = FIELD_OFFSET_LEFT_PX + i32::try_from(x * BLOCK_WIDTH_PX).unwrap();
-                let actual_y = FIELD_OFFSET_TOP_PX + i32::try_from((y + y_offset) * BLOCK_HEIGHT_PX).unwrap();
-                canvas.copy(
-                    &block_textures[shadow_color_index],
-                    None,
-                    Rect::new(
-                        actual_x,
-                        actual_y,
-                        BLOCK_WIDTH_PX,
-                        BLOCK_HEIGHT_PX,
-                    ),
-                ).unwrap();
-            }
+        for (x, y, color_index) in shadow_blocks(field) {
+            let shadow_color_index = BLOCK_COLOR_COUNT + 1 + usize::from(color_index);
+            let actual_x = FIELD_OFFSET_LEFT_PX + i32::try_from(x * BLOCK_WIDTH_PX).unwrap();
+            let actual_y = FIELD_OFFSET_TOP_PX + i32::try_from(y * BLOCK_HEIGHT_PX).unwrap();
+            renderer.blit_sprite(
+                &block_textures[shadow_color_index],
+                actual_x,
+                actual_y,
+                BLOCK_WIDTH_PX,
+                BLOCK_HEIGHT_PX,
+            );
         }
     }
 
+    // draw the ghost of the hinted piece
+    for &(x, y, color_index) in ghost_blocks {
+        let shadow_color_index = BLOCK_COLOR_COUNT + 1 + usize::from(color_index);
+        let actual_x = FIELD_OFFSET_LEFT_PX + i32::try_from(x * BLOCK_WIDTH_PX).unwrap();
+        let actual_y = FIELD_OFFSET_TOP_PX + i32::try_from(y * BLOCK_HEIGHT_PX).unwrap();
+        renderer.blit_sprite(
+            &block_textures[shadow_color_index],
+            actual_x,
+            actual_y,
+            BLOCK_WIDTH_PX,
+            BLOCK_HEIGHT_PX,
+        );
+    }
+
     let blocks_and_coords = field.blocks().iter().zip(Field::coords());
     let mut sequences = BTreeSet::new();
     for (field_block, (x, y)) in blocks_and_coords {
         if let FieldBlock::Block(block) = field_block {
             let base_color_index = usize::from(block.color_index);
-            let color_index = if let Some(counter) = block.state.disappearing_counter() {
-                if (counter & (1 << 3)) == 0 {
-                    base_color_index
-                } else {
-                    BLOCK_COLOR_COUNT
-                }
-            } else {
-                base_color_index
+            let mut actual_x = FIELD_OFFSET_LEFT_PX + i32::try_from(x * BLOCK_WIDTH_PX).unwrap();
+            let mut actual_y = FIELD_OFFSET_TOP_PX + (motion.drawn_y(x, y) * f64::from(BLOCK_HEIGHT_PX)).round() as i32;
+            let mut width = BLOCK_WIDTH_PX;
+            let mut height = BLOCK_HEIGHT_PX;
+            let sprite = match block.state.disappearing_counter() {
+                None => &block_textures[base_color_index],
+                Some(counter) => match sprites.clear_animation {
+                    ClearAnimation::Blink => if (counter & (1 << 3)) == 0 {
+                        &block_textures[base_color_index]
+                    } else {
+                        &block_textures[BLOCK_COLOR_COUNT]
+                    },
+                    ClearAnimation::Shrink => {
+                        let counter_u32: u32 = counter.try_into().unwrap();
+                        let blink_count_u32: u32 = DISAPPEAR_BLINK_COUNT.try_into().unwrap();
+                        width = BLOCK_WIDTH_PX * counter_u32 / blink_count_u32;
+                        height = BLOCK_HEIGHT_PX * counter_u32 / blink_count_u32;
+                        actual_x += i32::try_from((BLOCK_WIDTH_PX - width) / 2).unwrap();
+                        actual_y += i32::try_from((BLOCK_HEIGHT_PX - height) / 2).unwrap();
+                        &block_textures[base_color_index]
+                    },
+                    ClearAnimation::Fade|ClearAnimation::Dissolve|ClearAnimation::Highlight => {
+                        let stages = &sprites.clear_stages[base_color_index];
+                        &stages[crate::clear_animation::stage_index(counter, stages.len())]
+                    },
+                },
             };
-
-            let actual_x = FIELD_OFFSET_LEFT_PX + i32::try_from(x * BLOCK_WIDTH_PX).unwrap();
-            let actual_y = FIELD_OFFSET_TOP_PX + i32::try_from(y * BLOCK_HEIGHT_PX).unwrap();
-            canvas.copy(
-                &block_textures[color_index],
-                None,
-                Rect::new(
-                    actual_x,
-                    actual_y,
-                    BLOCK_WIDTH_PX,
-                    BLOCK_HEIGHT_PX,
-                ),
-            ).unwrap();
+            renderer.blit_sprite(sprite, actual_x, actual_y, width, height);
 
             if let Some(seq) = block.state.disappearing_sequence() {
                 if seq.len() > 0 {
@@ -216,8 +570,7 @@ fn draw(
             let &(last_x, last_y) = seq.last().unwrap();
 
             // draw a line between the two
-            canvas.set_draw_color(Color::WHITE);
-            canvas.draw_line(
+            renderer.draw_line(
                 (
                     FIELD_OFFSET_LEFT_PX + i32::try_from(first_x * BLOCK_WIDTH_PX + BLOCK_WIDTH_PX/2).unwrap(),
                     FIELD_OFFSET_TOP_PX + i32::try_from(first_y * BLOCK_HEIGHT_PX + BLOCK_HEIGHT_PX/2).unwrap(),
@@ -226,12 +579,15 @@ fn draw(
                     FIELD_OFFSET_LEFT_PX + i32::try_from(last_x * BLOCK_WIDTH_PX + BLOCK_WIDTH_PX/2).unwrap(),
                     FIELD_OFFSET_TOP_PX + i32::try_from(last_y * BLOCK_HEIGHT_PX + BLOCK_HEIGHT_PX/2).unwrap(),
                 ),
-            ).unwrap();
+                Rgba::WHITE,
+            );
         }
     }
 
+    effects.draw(renderer, &sprites.palette);
+
     // draw score
-    let mut my_score = score;
+    let mut my_score = game.score;
     let mut score_digits = [0u8; 4];
     for i in (0..score_digits.len()).rev() {
         score_digits[i] = u8::try_from(my_score % 10).unwrap();
@@ -242,27 +598,50 @@ fn draw(
         .map(|(i, &dig)| SegmentedDisplay::new(
             SCORE_OFFSET_LEFT_PX + i32::try_from(i).unwrap() * crate::seg_display::DIGIT_OFFSET,
             FIELD_OFFSET_TOP_PX,
-            Color::RGB(0x00, 0x7F, 0x00),
+            Rgba::rgb(0x00, 0x7F, 0x00),
             dig,
         ));
     for seg in segs {
-        seg.draw(canvas);
+        seg.draw(renderer);
+    }
+
+    // draw remaining hints
+    if let Some(remaining) = hints_remaining {
+        let hint_digits = [
+            u8::try_from((remaining / 10) % 10).unwrap(),
+            u8::try_from(remaining % 10).unwrap(),
+        ];
+        let hint_segs = hint_digits.iter()
+            .enumerate()
+            .map(|(i, &dig)| SegmentedDisplay::new(
+                HINT_COUNTER_OFFSET_LEFT_PX + i32::try_from(i).unwrap() * crate::seg_display::DIGIT_OFFSET,
+                FIELD_OFFSET_TOP_PX,
+                Rgba::rgb(0x00, 0x5F, 0x7F),
+                dig,
+            ));
+        for seg in hint_segs {
+            seg.draw(renderer);
+        }
     }
 
     // draw color stats
-    for (i, &color_count) in color_stats.iter().enumerate() {
+    for (i, &color_count) in game.color_stats.iter().enumerate() {
+        let x = COLOR_STATS_BARS_LEFT_PX + i32::try_from(i).unwrap() * i32::try_from(COLOR_STATS_BAR_WIDTH + COLOR_STATS_BAR_SPACING).unwrap();
+        if let Some(symbol) = sprites.symbols.get(i) {
+            // label the bar below the bottom of the field
+            let symbol_y = FIELD_OFFSET_TOP_PX + i32::try_from(FIELD_HEIGHT_BLOCKS * BLOCK_HEIGHT_PX + COLOR_STATS_BAR_SPACING).unwrap();
+            renderer.blit_sprite(symbol, x, symbol_y, COLOR_STATS_BAR_WIDTH, COLOR_STATS_BAR_WIDTH);
+        }
         if color_count == 0 {
             continue;
         }
 
-        let x = COLOR_STATS_BARS_LEFT_PX + i32::try_from(i).unwrap() * i32::try_from(COLOR_STATS_BAR_WIDTH + COLOR_STATS_BAR_SPACING).unwrap();
         let y = FIELD_OFFSET_TOP_PX + i32::try_from(FIELD_HEIGHT_BLOCKS * BLOCK_HEIGHT_PX - color_count).unwrap();
 
-        canvas.set_draw_color(BLOCK_COLORS[i]);
-        canvas.fill_rect(Rect::new(x, y, COLOR_STATS_BAR_WIDTH, color_count)).unwrap();
+        renderer.fill_rect(x, y, COLOR_STATS_BAR_WIDTH, color_count, sprites.palette[i]);
     }
 
-    match game_state {
+    match game.state {
         GameState::Play => {},
         GameState::Pause => {
             // draw two parallel vertical rectangles to indicate pause
@@ -271,11 +650,9 @@ fn draw(
             let x2 = x1 + 2*i32::try_from(PAUSE_BAR_WIDTH).unwrap();
             let y: i32 = ((WINDOW_HEIGHT - PAUSE_BAR_HEIGHT) / 2).try_into().unwrap();
 
-            let mut translucent_gray = Color::GRAY;
-            translucent_gray.a = 0xCC;
-            canvas.set_draw_color(translucent_gray);
-            canvas.fill_rect(Rect::new(x1, y, PAUSE_BAR_WIDTH, PAUSE_BAR_HEIGHT)).unwrap();
-            canvas.fill_rect(Rect::new(x2, y, PAUSE_BAR_WIDTH, PAUSE_BAR_HEIGHT)).unwrap();
+            let translucent_gray = Rgba::GRAY.with_alpha(0xCC);
+            renderer.fill_rect(x1, y, PAUSE_BAR_WIDTH, PAUSE_BAR_HEIGHT, translucent_gray);
+            renderer.fill_rect(x2, y, PAUSE_BAR_WIDTH, PAUSE_BAR_HEIGHT, translucent_gray);
         },
         GameState::Over => {
             // draw a square to indicate game over
@@ -283,32 +660,63 @@ fn draw(
             let x: i32 = ((WINDOW_WIDTH - total_width) / 2).try_into().unwrap();
             let y: i32 = ((WINDOW_HEIGHT - PAUSE_BAR_HEIGHT) / 2).try_into().unwrap();
 
-            let mut translucent_gray = Color::GRAY;
-            translucent_gray.a = 0xCC;
-            canvas.set_draw_color(translucent_gray);
-            canvas.fill_rect(Rect::new(x, y, total_width, PAUSE_BAR_HEIGHT)).unwrap();
+            let translucent_gray = Rgba::GRAY.with_alpha(0xCC);
+            renderer.fill_rect(x, y, total_width, PAUSE_BAR_HEIGHT, translucent_gray);
+        },
+        GameState::Title => {
+            let total_width = PAUSE_BAR_WIDTH * 3;
+            let x: i32 = ((WINDOW_WIDTH - total_width) / 2).try_into().unwrap();
+            let y: i32 = ((WINDOW_HEIGHT - PAUSE_BAR_HEIGHT) / 2).try_into().unwrap();
+            let translucent_gray = Rgba::GRAY.with_alpha(0xCC);
+
+            // label the demo above the triangle and tell how to leave it below, like the terminal
+            let text_height = i32::try_from(GLYPH_HEIGHT * TITLE_TEXT_SCALE).unwrap();
+            let draw_centered = |renderer: &mut R, text_y: i32, text: &str| {
+                let text_x = i32::try_from((WINDOW_WIDTH - text_width(text) * TITLE_TEXT_SCALE) / 2).unwrap();
+                draw_text(renderer, text_x + TITLE_TEXT_SHADOW_OFFSET_PX, text_y + TITLE_TEXT_SHADOW_OFFSET_PX, TITLE_TEXT_SCALE, Rgba::BLACK, text);
+                draw_text(renderer, text_x, text_y, TITLE_TEXT_SCALE, Rgba::WHITE, text);
+            };
+            draw_centered(renderer, y - TITLE_TEXT_SPACING_PX - text_height, "DEMO");
+
+            // draw a blinking triangle to indicate that a key starts the game
+            if (game.frame_count / TITLE_BLINK_FRAMES) % 2 == 0 {
+                draw_centered(renderer, y + i32::try_from(PAUSE_BAR_HEIGHT).unwrap() + TITLE_TEXT_SPACING_PX, "PRESS ANY KEY TO PLAY");
+
+                let half_height = PAUSE_BAR_HEIGHT / 2;
+                for row in 0..PAUSE_BAR_HEIGHT {
+                    let distance_from_middle = row.abs_diff(half_height);
+                    let width = total_width * (half_height - distance_from_middle.min(half_height)) / half_height;
+                    if width == 0 {
+                        continue;
+                    }
+                    renderer.fill_rect(x, y + i32::try_from(row).unwrap(), width, 1, translucent_gray);
+                }
+            }
         },
     }
 
-    canvas.present();
+    renderer.present();
 }
 
 
-/// Creates block textures and returns them in a predefined sequence.
+/// Creates block images in the colors of the palette (with the symbol of each color if
+/// `symbols` is set) and of the given size and returns them in a predefined sequence.
 ///
 /// The sequence is:
 /// * 0..BLOCK_COLOR_COUNT: the individual block colors
 /// * BLOCK_COLOR_COUNT: the highlight color for successful sequences
 /// * BLOCK_COLOR_COUNT+1..BLOCK_COLOR_COUNT+1+BLOCK_COLOR_COUNT: translucent colors for the shadow
-fn make_block_textures<'a, T>(creator: &'a TextureCreator<T>) -> Vec<Texture<'a>> {
+fn make_block_images(palette: &[Rgba; BLOCK_COLOR_COUNT], symbols: bool, width: u32, height: u32) -> Vec<Image> {
+    let center_offset_x = BLOCK_CENTER_OFFSET * width / BLOCK_WIDTH_PX;
+    let center_offset_y = BLOCK_CENTER_OFFSET * height / BLOCK_HEIGHT_PX;
     let mut ret = Vec::with_capacity(2*BLOCK_COLOR_COUNT+1);
     let mut shadow_colors = Vec::with_capacity(BLOCK_COLOR_COUNT);
-    for color in BLOCK_COLORS.into_iter().chain(once(Color::WHITE)) {
+    for (color_index, &color) in palette.iter().chain(once(&Rgba::WHITE)).enumerate() {
         let mid_color = mul_div_rgb(color, 4, 6);
         let dark_color = mul_div_rgb(color, 3, 6);
-        let pixel_count: usize = (BLOCK_WIDTH_PX * BLOCK_HEIGHT_PX)
+        let pixel_count: usize = (width * height)
             .try_into().unwrap();
-        let width_usize: usize = BLOCK_WIDTH_PX.try_into().unwrap();
+        let width_usize: usize = width.try_into().unwrap();
 
         // start texture with dark color
         // dddddddd
@@ -322,10 +730,10 @@ fn make_block_textures<'a, T>(creator: &'a TextureCreator<T>) -> Vec<Texture<'a>
         // lllllddd
         // lllddddd
         // lddddddd
-        for y_u32 in 0..BLOCK_HEIGHT_PX {
+        for y_u32 in 0..height {
             let y: usize = y_u32.try_into().unwrap();
 
-            let end_px = BLOCK_WIDTH_PX - (y_u32 * BLOCK_WIDTH_PX / BLOCK_HEIGHT_PX);
+            let end_px = width - (y_u32 * width / height);
             for x_u32 in 0..end_px {
                 let x: usize = x_u32.try_into().unwrap();
                 texture_colors[y*width_usize + x] = color;
@@ -337,55 +745,38 @@ fn make_block_textures<'a, T>(creator: &'a TextureCreator<T>) -> Vec<Texture<'a>
         // llmmmmdd
         // llmmmmdd
         // lddddddd
-        for y_u32 in BLOCK_CENTER_OFFSET..(BLOCK_HEIGHT_PX-BLOCK_CENTER_OFFSET) {
+        for y_u32 in center_offset_y..(height-center_offset_y) {
             let y: usize = y_u32.try_into().unwrap();
 
-            for x_u32 in BLOCK_CENTER_OFFSET..(BLOCK_WIDTH_PX-BLOCK_CENTER_OFFSET) {
+            for x_u32 in center_offset_x..(width-center_offset_x) {
                 let x: usize = x_u32.try_into().unwrap();
                 texture_colors[y*width_usize + x] = mid_color;
             }
         }
 
-        // squeeze into texture
-        let mut texture_data = Vec::with_capacity(texture_colors.len() * 4);
-        let mut shadow_data = Vec::with_capacity(texture_colors.len() * 4);
-        for color in texture_colors {
-            texture_data.push(color.r);
-            texture_data.push(color.g);
-            texture_data.push(color.b);
-            texture_data.push(color.a);
-
-            shadow_data.push(color.r / 3);
-            shadow_data.push(color.g / 3);
-            shadow_data.push(color.b / 3);
-            shadow_data.push(color.a);
-        }
+        let mut shadow_texture_colors: Vec<Rgba> = texture_colors.iter()
+            .map(|color| Rgba::new(color.r / 3, color.g / 3, color.b / 3, color.a))
+            .collect();
 
-        let mut texture = creator.create_texture(
-            Some(PixelFormatEnum::ABGR8888),
-            TextureAccess::Static,
-            BLOCK_WIDTH_PX, BLOCK_HEIGHT_PX,
-        ).unwrap();
-        texture.update(
-            Rect::new(0, 0, BLOCK_WIDTH_PX, BLOCK_HEIGHT_PX),
-            &texture_data,
-            (BLOCK_WIDTH_PX * 4).try_into().unwrap(),
-        ).unwrap();
-        ret.push(texture);
+        // the highlight color has no symbol
+        if symbols && color_index < BLOCK_COLOR_COUNT {
+            let symbol = SYMBOLS[color_index];
+            draw_symbol(&mut texture_colors, width, height, symbol, contrasting_color(mid_color));
+            draw_symbol(&mut shadow_texture_colors, width, height, symbol, color);
+        }
+        ret.push(Image {
+            width,
+            height,
+            pixels: texture_colors,
+        });
 
         // don't make a shadow for the highlight color
         if shadow_colors.len() < BLOCK_COLOR_COUNT {
-            let mut shadow_texture = creator.create_texture(
-                Some(PixelFormatEnum::ABGR8888),
-                TextureAccess::Static,
-                BLOCK_WIDTH_PX, BLOCK_HEIGHT_PX,
-            ).unwrap();
-            shadow_texture.update(
-                Rect::new(0, 0, BLOCK_WIDTH_PX, BLOCK_HEIGHT_PX),
-                &shadow_data,
-                (BLOCK_WIDTH_PX * 4).try_into().unwrap(),
-            ).unwrap();
-            shadow_colors.push(shadow_texture);
+            shadow_colors.push(Image {
+                width,
+                height,
+                pixels: shadow_texture_colors,
+            });
         }
     }
 
@@ -395,231 +786,435 @@ fn make_block_textures<'a, T>(creator: &'a TextureCreator<T>) -> Vec<Texture<'a>
 }
 
 
-fn handle_descending_blocks(field: &mut Field, descending_block_coords: &[(u32, u32)]) {
-    for &(x, y) in descending_block_coords {
-        let this_block = field.block_by_coord(x, y);
+fn danger_thresholds(opts: &Opts) -> DangerThresholds {
+    DangerThresholds {
+        spawn_column_height: opts.danger_spawn_column_height,
+        tower_height: opts.danger_tower_height,
+    }
+}
+
 
-        if field.block_at_coord_hit_bottom_or_stationary_block(x, y) {
-            // we are no longer descending
-            field.block_by_coord_mut(x, y)
-                .as_block_mut().unwrap()
-                .state = BlockState::Stationary;
-        } else {
-            *field.block_by_coord_mut(x, y + 1) = this_block.clone();
-            *field.block_by_coord_mut(x, y) = FieldBlock::Background;
-        }
+/// Asks the bot (if one is running) or the built-in AI for the best move for the descending blocks.
+fn pick_best_move(opts: &Opts, field: &Field, bot: Option<&mut Bot>, ai_rng: &mut StdRng) -> Option<BestMove> {
+    if let Some(bot) = bot {
+        return match bot.pick_best_move(field) {
+            Ok(best_move) => Some(best_move),
+            Err(e) => {
+                print_error(opts, &format!("bot failed to pick a move: {}\n", e));
+                None
+            },
+        };
+    }
+
+    let thread_count = crate::ai::effective_thread_count(opts.ai_threads);
+    match opts.ai_strategy {
+        AiStrategy::Greedy => crate::ai::pick_best_move(field, thread_count),
+        AiStrategy::Mcts => crate::ai::mcts::pick_best_move(
+            field,
+            Duration::from_millis(opts.ai_time_budget_ms),
+            thread_count,
+            ai_rng,
+        ),
+        AiStrategy::Adaptive => crate::ai::adaptive::pick_best_move(field, &danger_thresholds(opts), thread_count),
+    }
+}
+
+
+/// Returns the result of a command that has succeeded; otherwise, prints what failed along with the
+/// error and exits.
+fn or_exit<T>(result: io::Result<T>, what: &str) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{}: {}", what, e);
+            std::process::exit(1);
+        },
     }
 }
 
 
+/// Fills in the settings that were not given on the command line from the configuration file,
+/// after saving the given ones into it if requested.
+///
+/// Returns a message describing the problem if the file cannot be read or written.
+fn apply_config(opts: &mut Opts) -> Result<(), String> {
+    let path = match opts.config.clone().or_else(Config::default_path) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut config = Config::load(&path)
+        .map_err(|e| format!("failed to read configuration file {}: {}", path.display(), e))?;
+    if opts.save_config {
+        config.clear_animation = opts.clear_animation.or(config.clear_animation);
+        config.no_flash = opts.no_flash.or(config.no_flash);
+        config.effects = opts.effects.clone().or(config.effects);
+        config.save(&path)
+            .map_err(|e| format!("failed to save configuration file {}: {}", path.display(), e))?;
+        println!("saved settings to {}", path.display());
+    }
+    opts.clear_animation = opts.clear_animation.or(config.clear_animation);
+    opts.no_flash = opts.no_flash.or(config.no_flash);
+    opts.effects = opts.effects.take().or(config.effects);
+    Ok(())
+}
+
+
 fn main() {
-    let opts = Opts::parse();
-    let mut rng = {
-        let seed_value: u128 = if let Some(seed) = opts.random_seed {
-            seed
-        } else {
-            let mut trng = thread_rng();
-            trng.gen()
-        };
-        println!("RNG seed: {}", seed_value);
+    let mut opts = Opts::parse();
+
+    // the configured settings only affect how the game is shown
+    let shows_game = matches!(opts.command, None | Some(Command::Render { .. }) | Some(Command::Export { .. }));
+    if shows_game {
+        if let Err(message) = apply_config(&mut opts) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    }
+    if opts.bot.is_some() && !opts.ai {
+        opts.autoplay = true;
+    }
+    match &opts.command {
+        Some(Command::Env { count, threads }) => {
+            let thread_count = crate::ai::effective_thread_count(*threads);
+            crate::env::serve(*count, thread_count).expect("failed to serve environments");
+            return;
+        },
+        Some(Command::Analyze { file, score_threshold, sequence_threshold, height_threshold }) => {
+            let thresholds = crate::analysis::Thresholds {
+                criteria: [*score_threshold, *sequence_threshold, *height_threshold],
+            };
+            or_exit(crate::analysis::analyze(file, &thresholds), &format!("failed to analyze record file {}", file.display()));
+            return;
+        },
+        Some(Command::Solve { file }) => {
+            let solvable = or_exit(crate::puzzle::solve_file(file), &format!("failed to read puzzle file {}", file.display()));
+            if !solvable {
+                std::process::exit(1);
+            }
+            return;
+        },
+        Some(Command::Generate { dir, seed, count, max_pieces, max_height, colors, min_difficulty, max_attempts }) => {
+            let seed_value: u128 = seed.unwrap_or_else(|| thread_rng().gen());
+            println!("RNG seed: {}", seed_value);
+            let settings = crate::puzzle::generate::Settings {
+                max_pieces: *max_pieces,
+                max_height: *max_height,
+                color_count: *colors,
+                min_difficulty: *min_difficulty,
+                max_attempts: *max_attempts,
+            };
+            or_exit(crate::puzzle::generate::generate_files(seed_value, *count, dir, &settings), "failed to generate puzzles");
+            return;
+        },
+        Some(Command::Tournament { contestants, games, seed, max_pieces, time_budget_ms, threads }) => {
+            let settings = crate::tournament::Settings {
+                games: *games,
+                first_seed: *seed,
+                max_pieces: *max_pieces,
+                time_budget: Duration::from_millis(*time_budget_ms),
+                thread_count: crate::ai::effective_thread_count(*threads),
+            };
+            or_exit(crate::tournament::run(contestants, &settings), "failed to run tournament");
+            return;
+        },
+        Some(Command::Export { record, output, game, first_move, last_move, frame_skip, scale }) => {
+            let settings = crate::export::Settings {
+                game: *game,
+                first_move: *first_move,
+                last_move: *last_move,
+                frame_skip: *frame_skip,
+                scale_percent: *scale,
+            };
+            let (record, output) = (record.clone(), output.clone());
+
+            // drawing depends on options such as --shadows
+            OPTS.set(opts).expect("OPTS already set?!");
+            load_theme();
+            let frames = or_exit(crate::export::export(&record, &output, &settings), "failed to export replay");
+            println!("wrote {} frames", frames);
+            return;
+        },
+        Some(Command::Render { output, from_record, puzzle, game, move_index, seed }) => {
+            let source = if let Some(path) = from_record {
+                crate::screenshot::Source::Record { path: path.clone(), game: *game, move_index: *move_index }
+            } else if let Some(path) = puzzle {
+                crate::screenshot::Source::Puzzle { path: path.clone() }
+            } else {
+                crate::screenshot::Source::NewGame { seed: *seed, pieces: *move_index }
+            };
+            let output = output.clone();
+
+            // drawing depends on options such as --shadows
+            OPTS.set(opts).expect("OPTS already set?!");
+            load_theme();
+            or_exit(crate::screenshot::render_file(&source, &output), "failed to render game state");
+            return;
+        },
+        None => {},
+    }
 
-        let mut rng_seed_bytes = [0u8; 32];
-        rng_seed_bytes[0..128/8].copy_from_slice(&seed_value.to_be_bytes());
-        StdRng::from_seed(rng_seed_bytes)
+    let seed_value: u128 = if let Some(seed) = opts.random_seed {
+        seed
+    } else {
+        let mut trng = thread_rng();
+        trng.gen()
     };
+    println!("RNG seed: {}", seed_value);
 
     OPTS.set(opts).expect("OPTS already set?!");
+    let opts = OPTS.get().unwrap();
+    load_theme();
+
+    if uses_tui(opts) {
+        let mut frontend = TuiFrontend::new().expect("failed to set up terminal");
+        run_game(opts, seed_value, &mut frontend);
+
+        // restore the terminal before showing what was printed during the game
+        drop(frontend);
+        eprint!("{}", HELD_MESSAGES.lock().unwrap());
+    } else {
+        #[cfg(feature = "sdl")]
+        run_sdl(opts, seed_value);
+    }
+}
+
 
+/// Loads the theme chosen in the options, exiting with a message if that fails.
+fn load_theme() {
+    if let Err(e) = crate::theme::load_current() {
+        let dir = OPTS.get().and_then(|opts| opts.theme.as_ref()).expect("only themes can fail to load");
+        eprintln!("failed to load theme {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+}
+
+
+/// Returns whether the game is played in the terminal rather than in a window.
+fn uses_tui(opts: &Opts) -> bool {
+    opts.tui || cfg!(not(feature = "sdl"))
+}
+
+
+/// Keeps messages from being printed over the terminal frontend: they go to standard error if
+/// that has been redirected and are held back until the game is over otherwise. Returns whether
+/// the message has been taken care of.
+fn divert_from_tui(opts: &Opts, text: &str) -> bool {
+    if !uses_tui(opts) {
+        return false;
+    }
+    if io::stderr().is_terminal() {
+        HELD_MESSAGES.lock().unwrap().push_str(text);
+    } else {
+        eprint!("{}", text);
+    }
+    true
+}
+
+
+/// Prints a message on standard output during the game.
+fn print_message(opts: &Opts, text: &str) {
+    if !divert_from_tui(opts, text) {
+        print!("{}", text);
+    }
+}
+
+
+/// Prints an error message on standard error during the game.
+fn print_error(opts: &Opts, text: &str) {
+    if !divert_from_tui(opts, text) {
+        eprint!("{}", text);
+    }
+}
+
+
+#[cfg(feature = "sdl")]
+fn run_sdl(opts: &Opts, seed_value: u128) {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
     let window = video_subsystem.window("Columns", WINDOW_WIDTH, WINDOW_HEIGHT)
         .position_centered()
+        .resizable()
+        .allow_highdpi()
         .build()
         .unwrap();
 
-    let color_distribution = Uniform::new(0, u8::try_from(BLOCK_COLOR_COUNT).unwrap());
-    let mut color_stats = [0u32; BLOCK_COLOR_COUNT];
-    let mut block_fall_counter = 0;
-    let mut block_fall_limit = DEFAULT_BLOCK_FALL_LIMIT;
-
-    let mut canvas = window.into_canvas().build().unwrap();
-    canvas.set_blend_mode(BlendMode::Blend);
+    let canvas = window.into_canvas().build().unwrap();
     let texture_maker = canvas.texture_creator();
-    let block_textures = make_block_textures(&texture_maker);
+    let event_pump = sdl_context.event_pump().unwrap();
+    let mut frontend = SdlFrontend::new(event_pump, canvas, &texture_maker);
+    run_game(opts, seed_value, &mut frontend);
+}
+
 
-    let mut field = Field::new();
-    let mut game_state = GameState::Play;
-    let mut score = 0;
+/// Runs the title screen and the games until the player quits.
+fn run_game<F: Frontend>(opts: &Opts, seed_value: u128, frontend: &mut F) {
+    let mut ai_rng = crate::game::ai_rng_from_seed(seed_value);
+
+    let mut bot = opts.bot.as_ref()
+        .map(|command_line| Bot::spawn(command_line).expect("failed to start bot"));
+    let mut recorder = opts.record.as_ref()
+        .map(|path| Recorder::create(path).expect("failed to create record file"));
+    let player = if !opts.autoplay {
+        Player::Human
+    } else if bot.is_some() {
+        Player::Bot
+    } else {
+        Player::Ai
+    };
+
+    let mut game = if opts.skip_title {
+        Game::new(seed_value)
+    } else {
+        Game::new_demo(thread_rng().gen())
+    };
+    let mut demo_rng = crate::game::ai_rng_from_seed(thread_rng().gen());
+    let mut hints = Hints::new(opts.hint_limit);
+    let mut animated_move: Option<AnimatedMove> = None;
 
-    let mut event_pump = sdl_context.event_pump().unwrap();
     'main_loop: loop {
         let mut force_draw = false;
 
-        // handle events
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    break 'main_loop;
+        // handle input
+        for input in frontend.poll_inputs() {
+            match input {
+                Input::Quit => break 'main_loop,
+                Input::Redraw => {
+                    force_draw = true;
                 },
-                Event::KeyDown { keycode: Some(kc), .. } => {
-                    match kc {
-                        Keycode::Escape => break 'main_loop,
-                        Keycode::Left|Keycode::A|Keycode::J => if game_state == GameState::Play {
-                            field.move_descending_blocks_left();
-                        },
-                        Keycode::Right|Keycode::D|Keycode::L => if game_state == GameState::Play {
-                            field.move_descending_blocks_right();
-                        },
-                        Keycode::Up|Keycode::W|Keycode::I => if game_state == GameState::Play {
-                            // cycle through colors
-                            field.rotate_descending_blocks();
-                        },
-                        Keycode::Down|Keycode::S|Keycode::K => if game_state == GameState::Play {
-                            field.hand_descending_blocks_to_gravity();
-                        },
-                        Keycode::F2 => {
-                            // restart game
-                            for field_block in field.blocks_mut() {
-                                *field_block = FieldBlock::Background;
-                            }
-                            for color_stat in &mut color_stats {
-                                *color_stat = 0;
-                            }
-                            score = 0;
-                            block_fall_limit = DEFAULT_BLOCK_FALL_LIMIT;
-                            game_state = GameState::Play;
-                        },
-                        Keycode::F3 => {
-                            // pause/unpause
-                            game_state = match game_state {
-                                GameState::Over => GameState::Over,
-                                GameState::Play => GameState::Pause,
-                                GameState::Pause => GameState::Play,
-                            };
-
-                            // force redraw to make sure the "pause" symbol appears
-                            force_draw = true;
-                        },
-                        _ => {},
+                Input::Screenshot => {
+                    let ghost_blocks = hints.ghost_blocks(&game);
+                    match crate::screenshot::save_screenshot(&game, &ghost_blocks, hints.remaining()) {
+                        Ok(path) => print_message(opts, &format!("saved screenshot to {}\n", path.display())),
+                        Err(e) => print_error(opts, &format!("failed to save screenshot: {}\n", e)),
                     }
                 },
-                Event::Window { win_event: WindowEvent::Exposed, .. } => {
+                _ if game.state == GameState::Title => {
+                    // any key ends the demo and starts the actual game
+                    game = Game::new(seed_value);
+                    hints.restart();
+                    animated_move = None;
+                },
+                Input::Left => if game.state == GameState::Play {
+                    game.field.move_descending_blocks_left();
+                },
+                Input::Right => if game.state == GameState::Play {
+                    game.field.move_descending_blocks_right();
+                },
+                Input::Rotate => if game.state == GameState::Play {
+                    // cycle through colors
+                    game.field.rotate_descending_blocks();
+                },
+                Input::Drop => if game.state == GameState::Play {
+                    game.drop_piece();
+                },
+                Input::NewGame => {
+                    // restart game
+                    if let Some(rec) = recorder.as_mut() {
+                        rec.game_ended(&game).expect("failed to write record file");
+                    }
+                    game.restart();
+                    hints.restart();
+                    animated_move = None;
+                },
+                Input::ToggleHint => {
+                    // show/hide hint
+                    hints.toggle();
                     force_draw = true;
                 },
-                _ => {},
+                Input::TogglePause => {
+                    // pause/unpause
+                    game.toggle_pause();
+
+                    // force redraw to make sure the "pause" symbol appears
+                    force_draw = true;
+                },
+                Input::OtherKey => {},
             }
         }
 
-        if game_state == GameState::Play {
-            let disappearing_block_coords = field
-                .block_coords_with_predicate(|bs| bs.is_disappearing());
-            if disappearing_block_coords.len() > 0 {
-                // count down
-                field.reduce_disappearing_blocks();
+        match game.tick() {
+            Some(GameEvent::PieceSpawned) if game.state == GameState::Title => {
+                // the AI plays the demo game like a human would
+                if let Some(best_move) = pick_best_move(opts, &game.field, None, &mut demo_rng) {
+                    animated_move = Some(AnimatedMove::new(&best_move, DEMO_FRAMES_PER_ACTION, DEMO_DELAY_FRAMES, true));
+                }
+            },
+            Some(GameEvent::PieceSpawned) => {
+                if let Some(rec) = recorder.as_mut() {
+                    rec.piece_spawned(&game, player);
+                }
+
+                hints.new_piece();
 
-                // continue immediately
-                block_fall_counter = block_fall_limit;
-            } else {
-                let gravity_block_coords = field
-                    .block_coords_with_predicate(|bs| bs.is_pulled_by_gravity());
-                if gravity_block_coords.len() > 0 {
-                    field.descend_gravity_blocks();
-
-                    // continue immediately
-                    block_fall_counter = block_fall_limit;
-                } else {
-                    if block_fall_counter == block_fall_limit {
-                        // handle descending blocks
-                        block_fall_counter = 0;
-
-                        let descending_block_coords = field
-                            .block_coords_with_predicate(|bs| bs.is_descending());
-                        handle_descending_blocks(&mut field, &descending_block_coords);
-
-                        if descending_block_coords.len() == 0 {
-                            // no more descending blocks
-
-                            // any sequences?
-                            let old_score_divided = score / SCORE_SPEEDUP_DIVISOR;
-                            let sequences_found = field.disappear_scoring_sequences(&mut score);
-                            if sequences_found {
-                                if block_fall_limit > 1 {
-                                    let new_score_divided = score / SCORE_SPEEDUP_DIVISOR;
-                                    if new_score_divided > old_score_divided {
-                                        // increase speed by lowering the limit
-                                        block_fall_limit -= 1;
-                                    }
-                                }
-
-                                // continue immediately
-                                block_fall_counter = block_fall_limit - 1;
+                if opts.ai || opts.autoplay {
+                    let best_move_opt = pick_best_move(opts, &game.field, bot.as_mut(), &mut ai_rng);
+                    if let Some(best_move) = best_move_opt {
+                        if opts.ai {
+                            let thread_count = crate::ai::effective_thread_count(opts.ai_threads);
+                            let explanation = if opts.ai_strategy == AiStrategy::Adaptive && bot.is_none() {
+                                Explanation::new_adaptive(&game.field, best_move, &danger_thresholds(opts), thread_count)
                             } else {
-                                if field.make_new_descending_block(&color_distribution, &mut rng, &mut color_stats) {
-                                    let opts = OPTS.get().expect("OPTS not set?!");
-                                    if opts.ai || opts.autoplay {
-                                        if let Some(best_move) = crate::ai::pick_best_move(&field) {
-                                            if opts.ai {
-                                                println!("AI says best move is: {:?}", best_move);
-                                            }
-                                            if opts.autoplay {
-                                                // transform the descending block as such
-                                                let column_delta = i32::try_from(best_move.column).unwrap() - i32::try_from(NEW_BLOCK_COLUMN).unwrap();
-                                                if column_delta > 0 {
-                                                    for _ in 0..column_delta {
-                                                        field.move_descending_blocks_right();
-                                                    }
-                                                } else if column_delta < 0 {
-                                                    for _ in 0..(-column_delta) {
-                                                        field.move_descending_blocks_left();
-                                                    }
-                                                }
-                                                for _ in 0..best_move.rotate_count {
-                                                    field.rotate_descending_blocks();
-                                                }
-
-                                                if opts.fast_autoplay {
-                                                    field.hand_descending_blocks_to_gravity();
-                                                }
-                                            }
-                                        }
-                                    }
-                                } else {
-                                    // GAME OVER
-                                    game_state = GameState::Over;
-
-                                    // force redraw to make sure the "stop" symbol appears
-                                    force_draw = true;
-                                }
-                            }
+                                Explanation::new(&game.field, best_move, thread_count)
+                            };
+                            let text = match opts.ai_output {
+                                AiOutput::Text => explanation.to_text(),
+                                AiOutput::Json => format!("{}\n", explanation.to_json()),
+                            };
+                            print_message(opts, &text);
+                        }
+                        if opts.autoplay {
+                            // transform the descending block as such
+                            animated_move = Some(AnimatedMove::new(
+                                &best_move,
+                                opts.autoplay_frames_per_action,
+                                opts.autoplay_delay_frames,
+                                opts.fast_autoplay,
+                            ));
                         }
                     }
-                    block_fall_counter += 1;
                 }
-            }
+            },
+            Some(GameEvent::GameOver) if game.is_demo => {
+                // start another demo game
+                game = Game::new_demo(thread_rng().gen());
+                animated_move = None;
+            },
+            Some(GameEvent::GameOver) => {
+                if let Some(rec) = recorder.as_mut() {
+                    rec.game_ended(&game).expect("failed to write record file");
+                }
+
+                // force redraw to make sure the "stop" symbol appears
+                force_draw = true;
+            },
+            None => {},
         }
 
-        if game_state == GameState::Play || force_draw {
-            // regulate color stats
-            if color_stats.iter().any(|stat| *stat >= (FIELD_HEIGHT_BLOCKS - 1) * BLOCK_HEIGHT_PX) {
-                let delta = color_stats.iter().map(|stat| *stat).min().unwrap();
-                if delta == 0 {
-                    // pathological case where some color simply didn't appear often enough
-                    // just reset all the stats
-                    for color_stat in &mut color_stats {
-                        *color_stat = 0;
-                    }
-                } else {
-                    for color_stat in &mut color_stats {
-                        *color_stat -= delta;
-                    }
-                }
+        if let Some(am) = animated_move.as_mut() {
+            if am.tick(&mut game) {
+                animated_move = None;
             }
+        }
+
+        if let Some(rec) = recorder.as_mut() {
+            rec.update(&game);
+        }
 
-            draw(&mut canvas, &field, game_state, score, &color_stats, &block_textures);
-            canvas.present();
+        if game.state == GameState::Play && hints.wants_hint() && Piece::from_field(&game.field).is_some() {
+            let best_move = pick_best_move(opts, &game.field, bot.as_mut(), &mut ai_rng);
+            hints.set_hint(&game, best_move);
+        }
+
+        if game.state.is_running() || force_draw {
+            let ghost_blocks = hints.ghost_blocks(&game);
+            frontend.draw(&game, &ghost_blocks, hints.remaining());
         }
 
         sleep(Duration::new(0, 1_000_000_000 / 60))
     }
+
+    if let Some(rec) = recorder.as_mut() {
+        rec.game_ended(&game).expect("failed to write record file");
+    }
 }
@@ -1,6 +1,9 @@
 use std::collections::VecDeque;
 use std::fmt;
+use std::ops::{Deref, DerefMut};
 
+use once_cell::sync::Lazy;
+use rand::{Rng, SeedableRng};
 use rand::distributions::{Distribution, Uniform};
 use rand::rngs::StdRng;
 
@@ -10,6 +13,21 @@ use crate::{
 };
 
 
+/// The number of distinct block states as distinguished by the Zobrist hash.
+const ZOBRIST_STATE_COUNT: usize = 4;
+
+/// The seed for the Zobrist keys; fixed so that hashes are stable between runs.
+const ZOBRIST_SEED: u64 = 0x436F_6C75_6D6E_7321;
+
+/// Random keys for each combination of field position, block color and block state.
+static ZOBRIST_KEYS: Lazy<Vec<u64>> = Lazy::new(|| {
+    let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
+    (0..FIELD_BLOCK_COUNT * BLOCK_COLOR_COUNT * ZOBRIST_STATE_COUNT)
+        .map(|_| rng.gen())
+        .collect()
+});
+
+
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub(crate) enum BlockState {
     #[default] Stationary,
@@ -66,6 +84,18 @@ impl BlockState {
             _ => None,
         }
     }
+
+    /// Returns the index of this state for the purposes of Zobrist hashing.
+    ///
+    /// The counter and sequence of disappearing blocks are not taken into account.
+    fn zobrist_index(&self) -> usize {
+        match self {
+            Self::Stationary => 0,
+            Self::Descending => 1,
+            Self::Gravity => 2,
+            Self::Disappearing { .. } => 3,
+        }
+    }
 }
 
 
@@ -110,6 +140,21 @@ impl FieldBlock {
         }
     }
 
+    /// Returns the Zobrist key of this block at the given position of the field, or 0 if it is
+    /// background.
+    pub fn zobrist_key(&self, x: u32, y: u32) -> u64 {
+        match self {
+            Self::Background => 0,
+            Self::Block(block) => {
+                let position = usize::try_from(y * FIELD_WIDTH_BLOCKS + x).unwrap();
+                let index =
+                    (position * BLOCK_COLOR_COUNT + usize::from(block.color_index)) * ZOBRIST_STATE_COUNT
+                    + block.state.zobrist_index();
+                ZOBRIST_KEYS[index]
+            },
+        }
+    }
+
     pub fn is_stationary_block(&self) -> bool {
         match self {
             Self::Block(block) => match block.state {
@@ -123,24 +168,68 @@ impl FieldBlock {
 }
 
 
+/// Mutable access to a block of a field, which keeps the Zobrist hash of the field up to date.
+///
+/// The key of the block's old state is XORed out of the hash when the access begins and the key
+/// of its new state is XORed in when the access ends.
+pub(crate) struct FieldBlockMut<'a> {
+    field_block: &'a mut FieldBlock,
+    zobrist_hash: &'a mut u64,
+    x: u32,
+    y: u32,
+}
+impl<'a> Deref for FieldBlockMut<'a> {
+    type Target = FieldBlock;
+
+    fn deref(&self) -> &Self::Target {
+        self.field_block
+    }
+}
+impl<'a> DerefMut for FieldBlockMut<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.field_block
+    }
+}
+impl<'a> Drop for FieldBlockMut<'a> {
+    fn drop(&mut self) {
+        *self.zobrist_hash ^= self.field_block.zobrist_key(self.x, self.y);
+    }
+}
+
+
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub(crate) struct Field {
     blocks: [FieldBlock; FIELD_BLOCK_COUNT],
+
+    /// The XOR of the Zobrist keys of all blocks, updated with every change of a block.
+    zobrist_hash: u64,
 }
 impl Field {
     pub fn new() -> Self {
         let blocks = std::array::from_fn(|_| FieldBlock::Background);
         Self {
             blocks,
+            zobrist_hash: 0,
         }
     }
 
+    fn index(x: u32, y: u32) -> usize {
+        usize::try_from(y * FIELD_WIDTH_BLOCKS + x).unwrap()
+    }
+
     pub fn block_by_coord(&self, x: u32, y: u32) -> &FieldBlock {
-        &self.blocks[usize::try_from(y * FIELD_WIDTH_BLOCKS + x).unwrap()]
+        &self.blocks[Self::index(x, y)]
     }
 
-    pub fn block_by_coord_mut(&mut self, x: u32, y: u32) -> &mut FieldBlock {
-        &mut self.blocks[usize::try_from(y * FIELD_WIDTH_BLOCKS + x).unwrap()]
+    pub fn block_by_coord_mut(&mut self, x: u32, y: u32) -> FieldBlockMut<'_> {
+        let field_block = &mut self.blocks[Self::index(x, y)];
+        self.zobrist_hash ^= field_block.zobrist_key(x, y);
+        FieldBlockMut {
+            field_block,
+            zobrist_hash: &mut self.zobrist_hash,
+            x,
+            y,
+        }
     }
 
     /// Returns a reference to the field's blocks.
@@ -148,9 +237,12 @@ impl Field {
         &self.blocks
     }
 
-    /// Returns a mutable reference to the field's blocks.
-    pub fn blocks_mut(&mut self) -> &mut [FieldBlock] {
-        &mut self.blocks
+    /// Returns the Zobrist hash of the field's contents.
+    ///
+    /// Unlike the derived `Hash`, this does not walk the sequences of disappearing blocks, and it
+    /// is not calculated anew but kept up to date as blocks change.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.zobrist_hash
     }
 
     /// Returns an iterator over all the (x, y) coordinates of the field.
@@ -178,11 +270,10 @@ impl Field {
 
     /// Swaps two blocks in the field.
     pub fn swap_blocks(&mut self, x1: u32, y1: u32, x2: u32, y2: u32) {
-        unsafe {
-            let ptr1 = self.block_by_coord_mut(x1, y1) as *mut _;
-            let ptr2 = self.block_by_coord_mut(x2, y2) as *mut _;
-            std::ptr::swap(ptr1, ptr2);
-        }
+        let old_keys = self.block_by_coord(x1, y1).zobrist_key(x1, y1) ^ self.block_by_coord(x2, y2).zobrist_key(x2, y2);
+        self.blocks.swap(Self::index(x1, y1), Self::index(x2, y2));
+        let new_keys = self.block_by_coord(x1, y1).zobrist_key(x1, y1) ^ self.block_by_coord(x2, y2).zobrist_key(x2, y2);
+        self.zobrist_hash ^= old_keys ^ new_keys;
     }
 
     /// Returns the coordinates of the next block if the sequence started by the given block
@@ -363,7 +454,8 @@ impl Field {
             };
             if current_count > 0 {
                 // reduce count by 1
-                let counter_ref = self.block_by_coord_mut(x, y)
+                let mut field_block = self.block_by_coord_mut(x, y);
+                let counter_ref = field_block
                     .as_block_mut().unwrap()
                     .state
                     .disappearing_counter_mut().unwrap();
@@ -622,7 +714,7 @@ impl Sequence {
 
 #[cfg(test)]
 mod tests {
-    use super::FieldCoords;
+    use super::{Block, BlockState, Field, FieldBlock, FieldCoords};
 
     #[test]
     fn test_field_coords() {
@@ -716,4 +808,51 @@ mod tests {
             assert_eq!(fc.next_back(), None);
         }
     }
+
+    #[test]
+    fn test_zobrist_hash() {
+        let empty = Field::new();
+        assert_eq!(empty.zobrist_hash(), 0);
+
+        let mut field = Field::new();
+        *field.block_by_coord_mut(1, 17) = FieldBlock::Block(Block {
+            color_index: 2,
+            state: BlockState::Stationary,
+        });
+        let one_block_hash = field.zobrist_hash();
+        assert_ne!(one_block_hash, 0);
+        assert_eq!(one_block_hash, field.clone().zobrist_hash());
+
+        // same block at a different position
+        let mut moved_field = field.clone();
+        moved_field.swap_blocks(1, 17, 2, 17);
+        assert_ne!(moved_field.zobrist_hash(), one_block_hash);
+
+        // incremental update
+        let mut recolored_field = field.clone();
+        let old_key = recolored_field.block_by_coord(1, 17).zobrist_key(1, 17);
+        recolored_field.block_by_coord_mut(1, 17).as_block_mut().unwrap().color_index = 3;
+        let new_key = recolored_field.block_by_coord(1, 17).zobrist_key(1, 17);
+        assert_eq!(recolored_field.zobrist_hash(), one_block_hash ^ old_key ^ new_key);
+        assert_ne!(recolored_field.zobrist_hash(), one_block_hash);
+
+        // the kept hash matches one calculated from scratch after the field has been played on
+        let mut played_field = recolored_field.clone();
+        for (x, color_index) in [(0, 3), (2, 3), (3, 1)] {
+            *played_field.block_by_coord_mut(x, 0) = FieldBlock::Block(Block {
+                color_index,
+                state: BlockState::Gravity,
+            });
+        }
+        played_field.immediately_drop_gravity_blocks();
+        let mut score = 0;
+        assert!(played_field.disappear_scoring_sequences(&mut score));
+        played_field.immediately_remove_disappearing_blocks();
+        played_field.immediately_drop_gravity_blocks();
+        let recalculated_hash = played_field.blocks().iter()
+            .zip(Field::coords())
+            .fold(0, |hash, (block, (x, y))| hash ^ block.zobrist_key(x, y));
+        assert_eq!(played_field.zobrist_hash(), recalculated_hash);
+        assert_ne!(played_field.zobrist_hash(), 0);
+    }
 }
@@ -0,0 +1,232 @@
+//! Smooth movement of the blocks between the cells of the field.
+//!
+//! The game moves blocks by whole cells: the descending piece once every `block_fall_limit`
+//! frames and blocks pulled by gravity once per frame. This is purely about drawing them: the
+//! descending piece is drawn between its cells according to `block_fall_counter`, and each block
+//! pulled by gravity follows its cell with a speed that starts slow and increases, catching up
+//! with the cell after it has landed.
+
+
+use crate::{FIELD_BLOCK_COUNT, FIELD_HEIGHT_BLOCKS, FIELD_WIDTH_BLOCKS};
+use crate::game::Game;
+use crate::model::{Field, FieldBlock};
+
+
+/// How fast blocks start falling when pulled by gravity, in cells per frame.
+const GRAVITY_START_SPEED: f64 = 0.2;
+
+/// How much faster blocks pulled by gravity fall with every frame, in cells per frame.
+const GRAVITY_ACCELERATION: f64 = 0.2;
+
+
+/// A block that is not descending, as drawn.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+struct TrackedBlock {
+    y: u32,
+    color_index: u8,
+    disappearing: bool,
+
+    /// The drawn vertical position in cells, which is above the cell while catching up with it.
+    drawn_y: f64,
+
+    /// The current speed in cells per frame.
+    speed: f64,
+}
+impl TrackedBlock {
+    /// Moves the drawn block towards its cell by one frame. A block drawn below its cell (such as
+    /// a piece dropped while drawn between cells) waits there while the cell is still moving.
+    fn advance(&mut self, cell_moved: bool) {
+        let target = f64::from(self.y);
+        if self.drawn_y >= target {
+            if !cell_moved {
+                self.drawn_y = target;
+                self.speed = 0.0;
+            }
+            return;
+        }
+        self.speed = if self.speed == 0.0 { GRAVITY_START_SPEED } else { self.speed + GRAVITY_ACCELERATION };
+        self.drawn_y = (self.drawn_y + self.speed).min(target);
+    }
+}
+
+
+/// Where the blocks of a game are drawn, which follows the game from frame to frame.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
+pub(crate) struct Motion {
+    /// For each column, the blocks that are not descending, from the bottom up.
+    columns: Vec<Vec<TrackedBlock>>,
+
+    /// For each column, the drawn vertical positions of the descending blocks, from the bottom up.
+    descending: Vec<Vec<f64>>,
+
+    /// The drawn vertical position of the block in each cell of the field, in cells.
+    drawn_ys: Vec<f64>,
+
+    /// The frame of the game that the motion has been updated to.
+    frame_count: Option<u64>,
+}
+impl Motion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follows the game to its current frame.
+    pub fn update(&mut self, game: &Game) {
+        if self.frame_count == Some(game.frame_count) {
+            return;
+        }
+        let elapsed_frames = match self.frame_count {
+            Some(previous) if previous < game.frame_count => game.frame_count - previous,
+            _ => 0,
+        };
+        self.frame_count = Some(game.frame_count);
+
+        let width: usize = FIELD_WIDTH_BLOCKS.try_into().unwrap();
+        self.columns.resize(width, Vec::new());
+        self.descending.resize(width, Vec::new());
+        self.drawn_ys = Field::coords()
+            .map(|(_x, y)| f64::from(y))
+            .collect();
+        assert_eq!(self.drawn_ys.len(), FIELD_BLOCK_COUNT);
+
+        let descending_offset = descending_offset(game);
+        for x in 0..FIELD_WIDTH_BLOCKS {
+            let column = usize::try_from(x).unwrap();
+
+            let mut new_blocks = Vec::new();
+            let mut new_descending = Vec::new();
+            for y in (0..FIELD_HEIGHT_BLOCKS).rev() {
+                if let FieldBlock::Block(block) = game.field.block_by_coord(x, y) {
+                    if block.state.is_descending() {
+                        new_descending.push(y);
+                    } else {
+                        new_blocks.push((y, block.color_index, block.state.is_disappearing()));
+                    }
+                }
+            }
+
+            // blocks only leave a column by disappearing, and only join it on top
+            let mut old_blocks = std::mem::take(&mut self.columns[column]);
+            if old_blocks.len() > new_blocks.len() {
+                old_blocks.retain(|block| !block.disappearing);
+            }
+            let blocks_match = old_blocks.len() <= new_blocks.len()
+                && old_blocks.iter().zip(&new_blocks).all(|(old, &(y, color_index, _))| old.color_index == color_index && old.y <= y);
+            if !blocks_match {
+                // e.g. a new game; start over
+                old_blocks.clear();
+            }
+
+            // blocks that join the column continue from where the descending piece was drawn
+            let joined_count = new_blocks.len() - old_blocks.len();
+            let old_descending = &self.descending[column];
+            let joined_from_piece = joined_count == old_descending.len();
+
+            let mut blocks: Vec<TrackedBlock> = Vec::with_capacity(new_blocks.len());
+            for (i, &(y, color_index, disappearing)) in new_blocks.iter().enumerate() {
+                let mut block = match old_blocks.get(i) {
+                    Some(&old) => old,
+                    None => {
+                        let drawn_y = if joined_from_piece {
+                            old_descending[i - old_blocks.len()]
+                        } else {
+                            f64::from(y)
+                        };
+                        TrackedBlock { y, color_index, disappearing, drawn_y, speed: 0.0 }
+                    },
+                };
+                let cell_moved = block.y != y || joined_from_piece;
+                block.y = y;
+                block.disappearing = disappearing;
+                for frame in 0..elapsed_frames {
+                    block.advance(cell_moved && frame == 0);
+                }
+
+                // don't overtake the block below
+                if let Some(below) = blocks.last() {
+                    block.drawn_y = block.drawn_y.min(below.drawn_y - 1.0);
+                }
+                self.drawn_ys[index(x, y)] = block.drawn_y;
+                blocks.push(block);
+            }
+            self.columns[column] = blocks;
+
+            self.descending[column] = new_descending.iter()
+                .map(|&y| f64::from(y) + descending_offset)
+                .collect();
+            for &y in &new_descending {
+                self.drawn_ys[index(x, y)] = f64::from(y) + descending_offset;
+            }
+        }
+    }
+
+    /// Returns the vertical position, in cells, at which the block in the cell is drawn.
+    pub fn drawn_y(&self, x: u32, y: u32) -> f64 {
+        self.drawn_ys.get(index(x, y))
+            .copied()
+            .unwrap_or(f64::from(y))
+    }
+}
+
+
+fn index(x: u32, y: u32) -> usize {
+    (y * FIELD_WIDTH_BLOCKS + x).try_into().unwrap()
+}
+
+
+/// Returns how far the descending piece has progressed towards the next cell, if it can fall
+/// further.
+fn descending_offset(game: &Game) -> f64 {
+    let descending_blocks = game.field.block_coords_with_predicate(|b| b.is_descending());
+    let lowest = match descending_blocks.iter().max_by_key(|&&(_x, y)| y) {
+        Some(&lowest) => lowest,
+        None => return 0.0,
+    };
+    if game.field.block_at_coord_hit_bottom_or_stationary_block(lowest.0, lowest.1) {
+        return 0.0;
+    }
+    let counter = game.block_fall_counter.min(game.block_fall_limit);
+    (counter as f64) / (game.block_fall_limit as f64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Motion;
+    use crate::FIELD_HEIGHT_BLOCKS;
+    use crate::game::Game;
+    use crate::model::{Block, BlockState, FieldBlock};
+
+    #[test]
+    fn test_gravity_eases_in() {
+        let mut game = Game::new(0);
+        *game.field.block_by_coord_mut(0, 0) = FieldBlock::Block(Block {
+            color_index: 2,
+            state: BlockState::Gravity,
+        });
+
+        let mut motion = Motion::new();
+        motion.update(&game);
+        assert_eq!(motion.drawn_y(0, 0), 0.0);
+
+        let mut previous_y = 0.0;
+        let mut previous_step = 0.0;
+        for _ in 0..4 {
+            game.tick();
+            motion.update(&game);
+            let logical_y = game.field.block_coords_with_predicate(|_| true)[0].1;
+            let drawn_y = motion.drawn_y(0, logical_y);
+            assert!(drawn_y <= f64::from(logical_y));
+            assert!(drawn_y - previous_y > previous_step);
+            previous_step = drawn_y - previous_y;
+            previous_y = drawn_y;
+        }
+
+        // the block lands on the grid eventually
+        for _ in 0..FIELD_HEIGHT_BLOCKS * 2 {
+            game.tick();
+        }
+        motion.update(&game);
+        assert_eq!(motion.drawn_y(0, FIELD_HEIGHT_BLOCKS - 1), f64::from(FIELD_HEIGHT_BLOCKS - 1));
+    }
+}
@@ -0,0 +1,82 @@
+//! A tiny bitmap font for the few words the game shows, drawn as filled rectangles.
+
+
+use crate::render::{Renderer, Rgba};
+
+
+/// The height of the glyphs in font pixels.
+pub(crate) const GLYPH_HEIGHT: u32 = 5;
+
+/// The space between glyphs in font pixels.
+const GLYPH_SPACING: u32 = 1;
+
+
+/// Returns the rows of the glyph for the character from the top, in which `#` is set, or `None` if
+/// the font doesn't contain the character.
+fn glyph(c: char) -> Option<[&'static str; GLYPH_HEIGHT as usize]> {
+    let rows = match c {
+        '0' => ["###", "#.#", "#.#", "#.#", "###"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["###", "..#", "###", "#..", "###"],
+        '3' => ["###", "..#", ".##", "..#", "###"],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' => ["###", "#..", "###", "..#", "###"],
+        '6' => ["###", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", ".#.", ".#.", ".#."],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "###"],
+        '+' => ["...", ".#.", "###", ".#.", "..."],
+        'x' => ["...", "#.#", ".#.", "#.#", "..."],
+        'A' => [".#.", "#.#", "###", "#.#", "#.#"],
+        'C' => ["###", "#..", "#..", "#..", "###"],
+        'D' => ["##.", "#.#", "#.#", "#.#", "##."],
+        'E' => ["###", "#..", "##.", "#..", "###"],
+        'H' => ["#.#", "#.#", "###", "#.#", "#.#"],
+        'I' => ["###", ".#.", ".#.", ".#.", "###"],
+        'K' => ["#.#", "#.#", "##.", "#.#", "#.#"],
+        'L' => ["#..", "#..", "#..", "#..", "###"],
+        'M' => ["#...#", "##.##", "#.#.#", "#...#", "#...#"],
+        'N' => ["#..#", "##.#", "#.##", "#..#", "#..#"],
+        'O' => [".#.", "#.#", "#.#", "#.#", ".#."],
+        'P' => ["##.", "#.#", "##.", "#..", "#.."],
+        'R' => ["##.", "#.#", "##.", "#.#", "#.#"],
+        'S' => [".##", "#..", ".#.", "..#", "##."],
+        'T' => ["###", ".#.", ".#.", ".#.", ".#."],
+        'Y' => ["#.#", "#.#", ".#.", ".#.", ".#."],
+        ' ' => ["..", "..", "..", "..", ".."],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+
+/// Returns the width of the text in font pixels.
+pub(crate) fn text_width(text: &str) -> u32 {
+    let glyph_widths: u32 = text.chars()
+        .filter_map(glyph)
+        .map(|rows| u32::try_from(rows[0].len()).unwrap())
+        .sum();
+    let glyph_count = u32::try_from(text.chars().filter_map(glyph).count()).unwrap();
+    glyph_widths + GLYPH_SPACING * glyph_count.saturating_sub(1)
+}
+
+
+/// Draws the text with its top left corner at the given coordinates, with every font pixel drawn as
+/// a square of `scale` pixels. Characters that the font doesn't contain are skipped.
+pub(crate) fn draw_text<R: Renderer>(renderer: &mut R, x: i32, y: i32, scale: u32, color: Rgba, text: &str) {
+    let scale_i32 = i32::try_from(scale).unwrap();
+    let mut glyph_x = x;
+    for rows in text.chars().filter_map(glyph) {
+        for (row_index, row) in rows.iter().enumerate() {
+            let pixel_y = y + i32::try_from(row_index).unwrap() * scale_i32;
+            for (column_index, pixel) in row.chars().enumerate() {
+                if pixel == '#' {
+                    let pixel_x = glyph_x + i32::try_from(column_index).unwrap() * scale_i32;
+                    renderer.fill_rect(pixel_x, pixel_y, scale, scale, color);
+                }
+            }
+        }
+        let glyph_width = i32::try_from(rows[0].len()).unwrap();
+        glyph_x += (glyph_width + i32::try_from(GLYPH_SPACING).unwrap()) * scale_i32;
+    }
+}
@@ -0,0 +1,457 @@
+//! Puzzles: a start field, a fixed list of pieces and a goal.
+//!
+//! Puzzle files are plain text. Empty lines and lines starting with `#` are ignored; the other
+//! lines are:
+//!
+//! ```text
+//! goal clear-all
+//! pieces 012 330 451
+//! field
+//! ......
+//! ..1...
+//! 001122
+//! ```
+//!
+//! * `goal`: `clear-all` (no blocks may remain), `clear-color C` (no blocks of color index `C` may
+//!   remain) or `score N` (a score of at least `N` must be reached)
+//! * `pieces`: the pieces in the order in which they appear, each as the color indexes of its
+//!   blocks from top to bottom
+//! * `field`: the rest of the file contains the rows of the field, from top to bottom, in the
+//!   encoding of the bot protocol (`.` is an empty cell and a digit is the color index of a
+//!   block); rows that are left out at the top are empty
+//!
+//! Blocks that float in the air fall down when the puzzle is loaded. The goal does not have to be
+//! reached using all the pieces.
+
+
+pub(crate) mod generate;
+
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{BLOCK_COLOR_COUNT, FIELD_HEIGHT_BLOCKS, FIELD_WIDTH_BLOCKS, NEW_BLOCK_COLUMN};
+use crate::ai::{BestMove, candidate_moves, settle_field, settle_field_with_chain_depth};
+use crate::analysis::side_by_side;
+use crate::model::{Block, BlockState, Field, FieldBlock};
+use crate::puzzle::generate::difficulty;
+
+
+/// What has to be achieved to solve a puzzle.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Goal {
+    /// No blocks may remain on the field.
+    ClearAll,
+
+    /// No blocks of the given color index may remain on the field.
+    ClearColor(u8),
+
+    /// The score has to reach at least the given value.
+    Score(u64),
+}
+impl Goal {
+    pub fn is_reached(&self, field: &Field, score: u64) -> bool {
+        match self {
+            Self::ClearAll => field.blocks().iter().all(|b| b.is_background()),
+            Self::ClearColor(color_index) => field.blocks().iter().all(|b| b.color_index() != Some(*color_index)),
+            Self::Score(target) => score >= *target,
+        }
+    }
+
+    /// Returns the part of the score that is relevant to this goal.
+    ///
+    /// Positions that only differ in an irrelevant part of the score are equivalent.
+    fn relevant_score(&self, score: u64) -> u64 {
+        match self {
+            Self::ClearAll|Self::ClearColor(_) => 0,
+            Self::Score(target) => score.min(*target),
+        }
+    }
+}
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClearAll => write!(f, "clear-all"),
+            Self::ClearColor(color_index) => write!(f, "clear-color {}", color_index),
+            Self::Score(target) => write!(f, "score {}", target),
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Puzzle {
+    pub goal: Goal,
+    pub field: Field,
+
+    /// The pieces in the order in which they appear, each as the color indexes of its blocks from
+    /// top to bottom.
+    pub pieces: Vec<Vec<u8>>,
+}
+impl Puzzle {
+    fn parse_color(c: char) -> Option<u8> {
+        let color_index: u8 = c.to_digit(10)?.try_into().unwrap();
+        if usize::from(color_index) < BLOCK_COLOR_COUNT {
+            Some(color_index)
+        } else {
+            None
+        }
+    }
+
+    /// Parses a puzzle in the format described at the top of this module.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut goal = None;
+        let mut pieces = None;
+        let mut rows: Option<Vec<&str>> = None;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(rows) = rows.as_mut() {
+                rows.push(line);
+                continue;
+            }
+
+            let mut pieces_iter = line.split_whitespace();
+            let keyword = pieces_iter.next().unwrap();
+            let args: Vec<&str> = pieces_iter.collect();
+            match (keyword, args.as_slice()) {
+                ("goal", ["clear-all"]) => goal = Some(Goal::ClearAll),
+                ("goal", ["clear-color", color]) => {
+                    let color_index = color.parse::<u8>().ok()
+                        .filter(|&c| usize::from(c) < BLOCK_COLOR_COUNT)
+                        .ok_or_else(|| format!("invalid color {:?}", color))?;
+                    goal = Some(Goal::ClearColor(color_index));
+                },
+                ("goal", ["score", target]) => {
+                    let target = target.parse()
+                        .map_err(|_| format!("invalid score {:?}", target))?;
+                    goal = Some(Goal::Score(target));
+                },
+                ("goal", _) => return Err(format!("invalid goal {:?}", line)),
+                ("pieces", piece_strings) => {
+                    let parsed: Option<Vec<Vec<u8>>> = piece_strings.iter()
+                        .map(|p| p.chars().map(Self::parse_color).collect())
+                        .collect();
+                    let parsed = parsed.ok_or_else(|| format!("invalid pieces {:?}", line))?;
+                    if parsed.iter().any(|p| p.is_empty() || p.len() > usize::try_from(FIELD_HEIGHT_BLOCKS).unwrap()) {
+                        return Err(format!("invalid pieces {:?}", line));
+                    }
+                    pieces = Some(parsed);
+                },
+                ("field", []) => rows = Some(Vec::new()),
+                _ => return Err(format!("unexpected line {:?}", line)),
+            }
+        }
+
+        let rows = rows.ok_or("missing field")?;
+        if rows.len() > usize::try_from(FIELD_HEIGHT_BLOCKS).unwrap() {
+            return Err(format!("field has more than {} rows", FIELD_HEIGHT_BLOCKS));
+        }
+        let mut field = Field::new();
+        let top_y = FIELD_HEIGHT_BLOCKS - u32::try_from(rows.len()).unwrap();
+        for (row_index, row) in rows.iter().enumerate() {
+            if row.chars().count() != usize::try_from(FIELD_WIDTH_BLOCKS).unwrap() {
+                return Err(format!("field row {:?} does not have {} cells", row, FIELD_WIDTH_BLOCKS));
+            }
+            let y = top_y + u32::try_from(row_index).unwrap();
+            for (x, cell) in (0..FIELD_WIDTH_BLOCKS).zip(row.chars()) {
+                if cell == '.' {
+                    continue;
+                }
+                let color_index = Self::parse_color(cell)
+                    .ok_or_else(|| format!("invalid cell {:?} in field row {:?}", cell, row))?;
+                *field.block_by_coord_mut(x, y) = FieldBlock::Block(Block {
+                    color_index,
+                    state: BlockState::Gravity,
+                });
+            }
+        }
+        field.immediately_drop_gravity_blocks();
+
+        Ok(Self {
+            goal: goal.ok_or("missing goal")?,
+            field,
+            pieces: pieces.ok_or("missing pieces")?,
+        })
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Returns the puzzle in the format described at the top of this module.
+    pub fn to_text(&self) -> String {
+        let mut ret = String::new();
+        ret.push_str(&format!("goal {}\n", self.goal));
+
+        ret.push_str("pieces");
+        for piece in &self.pieces {
+            ret.push(' ');
+            for color_index in piece {
+                ret.push_str(&color_index.to_string());
+            }
+        }
+        ret.push('\n');
+
+        ret.push_str("field\n");
+        let top_y = (0..FIELD_HEIGHT_BLOCKS)
+            .find(|&y| (0..FIELD_WIDTH_BLOCKS).any(|x| !self.field.block_by_coord(x, y).is_background()))
+            .unwrap_or(FIELD_HEIGHT_BLOCKS - 1);
+        for y in top_y..FIELD_HEIGHT_BLOCKS {
+            for x in 0..FIELD_WIDTH_BLOCKS {
+                match self.field.block_by_coord(x, y).color_index() {
+                    Some(color_index) => ret.push_str(&color_index.to_string()),
+                    None => ret.push('.'),
+                }
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+}
+
+
+/// Places a new piece at the top of the field as the game would. Returns `None` if there is no space
+/// for it.
+pub(crate) fn spawn_piece(field: &Field, colors: &[u8]) -> Option<Field> {
+    let mut spawned = field.clone();
+    for (y, &color_index) in (0..FIELD_HEIGHT_BLOCKS).zip(colors) {
+        let mut field_block = spawned.block_by_coord_mut(NEW_BLOCK_COLUMN, y);
+        if !field_block.is_background() {
+            return None;
+        }
+        *field_block = FieldBlock::Block(Block {
+            color_index,
+            state: BlockState::Descending,
+        });
+    }
+    Some(spawned)
+}
+
+
+/// A sequence of placements that reaches the goal of a puzzle.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Solution {
+    /// One move per piece, with the rotation counted from the orientation in which the piece
+    /// appears.
+    pub moves: Vec<BestMove>,
+
+    /// The score at the end, including any score gained from sequences in the start field.
+    pub score: u64,
+}
+
+
+/// The outcome of a search for a solution.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct SolveResult {
+    /// The shortest solution, or `None` if the puzzle cannot be solved.
+    pub solution: Option<Solution>,
+
+    /// How many positions were examined.
+    pub positions_searched: u64,
+}
+
+
+struct Solver<'a> {
+    puzzle: &'a Puzzle,
+    piece_limit: usize,
+
+    /// Positions (field, index of the next piece, relevant score) from which the goal cannot be
+    /// reached within the piece limit.
+    dead_ends: HashSet<(Field, usize, u64)>,
+
+    positions_searched: u64,
+    moves: Vec<BestMove>,
+}
+impl<'a> Solver<'a> {
+    fn search(&mut self, field: &Field, piece_index: usize, score: u64) -> Option<u64> {
+        self.positions_searched += 1;
+        if self.puzzle.goal.is_reached(field, score) {
+            return Some(score);
+        }
+        if piece_index >= self.piece_limit {
+            return None;
+        }
+
+        let key = (field.clone(), piece_index, self.puzzle.goal.relevant_score(score));
+        if self.dead_ends.contains(&key) {
+            return None;
+        }
+
+        if let Some(spawned) = spawn_piece(field, &self.puzzle.pieces[piece_index]) {
+            for (mut next_field, best_move) in candidate_moves(&spawned) {
+                let gained = settle_field(&mut next_field);
+                self.moves.push(best_move);
+                if let Some(final_score) = self.search(&next_field, piece_index + 1, score + gained) {
+                    return Some(final_score);
+                }
+                self.moves.pop();
+            }
+        }
+
+        self.dead_ends.insert(key);
+        None
+    }
+}
+
+
+/// Searches for the shortest sequence of placements that reaches the goal of the puzzle.
+///
+/// The search is exhaustive: if no solution is returned, none exists.
+pub(crate) fn solve(puzzle: &Puzzle) -> SolveResult {
+    let mut start_field = puzzle.field.clone();
+    let start_score = settle_field(&mut start_field);
+
+    let mut positions_searched = 0;
+
+    // deepen iteratively to find the shortest solution first
+    for piece_limit in 0..=puzzle.pieces.len() {
+        let mut solver = Solver {
+            puzzle,
+            piece_limit,
+            dead_ends: HashSet::new(),
+            positions_searched: 0,
+            moves: Vec::new(),
+        };
+        let found = solver.search(&start_field, 0, start_score);
+        positions_searched += solver.positions_searched;
+        if let Some(score) = found {
+            return SolveResult {
+                solution: Some(Solution {
+                    moves: solver.moves,
+                    score,
+                }),
+                positions_searched,
+            };
+        }
+    }
+
+    SolveResult {
+        solution: None,
+        positions_searched,
+    }
+}
+
+
+/// The state of a puzzle after a move has been replayed.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct ReplayStep {
+    /// The field once all sequences have been removed.
+    pub field: Field,
+
+    /// The score so far.
+    pub score: u64,
+
+    /// How many times in succession sequences were removed after the move.
+    pub chain_depth: u32,
+}
+
+
+/// Places the pieces of the puzzle according to the moves and returns the state after each move.
+///
+/// Returns `None` if one of the moves is not possible.
+pub(crate) fn replay(puzzle: &Puzzle, moves: &[BestMove]) -> Option<Vec<ReplayStep>> {
+    let mut field = puzzle.field.clone();
+    let mut score = settle_field(&mut field);
+
+    let mut steps = Vec::with_capacity(moves.len());
+    for (piece, best_move) in puzzle.pieces.iter().zip(moves) {
+        let spawned = spawn_piece(&field, piece)?;
+        let (next_field, _best_move) = candidate_moves(&spawned)
+            .into_iter()
+            .find(|(_field, bm)| bm == best_move)?;
+        field = next_field;
+        let (gained, chain_depth) = settle_field_with_chain_depth(&mut field);
+        score += gained;
+        steps.push(ReplayStep {
+            field: field.clone(),
+            score,
+            chain_depth,
+        });
+    }
+    Some(steps)
+}
+
+
+/// Solves the puzzle in the file and prints the solution along with the field after each move on
+/// standard output.
+///
+/// Returns whether the puzzle can be solved.
+pub(crate) fn solve_file(path: &Path) -> io::Result<bool> {
+    let puzzle = Puzzle::read(path)?;
+    println!("goal: {}", puzzle.goal);
+
+    let result = solve(&puzzle);
+    let solution = match result.solution {
+        Some(s) => s,
+        None => {
+            println!("no solution exists (searched {} positions)", result.positions_searched);
+            return Ok(false);
+        },
+    };
+    let steps = replay(&puzzle, &solution.moves)
+        .expect("solution cannot be replayed");
+    println!(
+        "solution with {} of {} pieces, score {}, difficulty {} (searched {} positions)",
+        solution.moves.len(), puzzle.pieces.len(), solution.score, difficulty(&steps),
+        result.positions_searched,
+    );
+
+    let mut titles = vec!["start".to_owned()];
+    for (i, ((piece, best_move), step)) in puzzle.pieces.iter().zip(&solution.moves).zip(&steps).enumerate() {
+        let piece_string: String = piece.iter().map(|c| c.to_string()).collect();
+        println!(
+            "piece {} ({}): column {} rotation {}, chain depth {}",
+            i + 1, piece_string, best_move.column, best_move.rotate_count, step.chain_depth,
+        );
+        titles.push(format!("{}", i + 1));
+    }
+
+    let mut titled_fields: Vec<(&str, &Field)> = vec![(&titles[0], &puzzle.field)];
+    for (title, step) in titles[1..].iter().zip(&steps) {
+        titled_fields.push((title, &step.field));
+    }
+    print!("{}", side_by_side(&titled_fields));
+
+    Ok(true)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Goal, Puzzle, replay, solve};
+
+    #[test]
+    fn test_solve() {
+        let text = "\
+            # two pieces are needed to clear the field\n\
+            goal clear-all\n\
+            pieces 222 111 555\n\
+            field\n\
+            .....2\n\
+            11...2\n\
+        ";
+        let puzzle = Puzzle::parse(text).unwrap();
+        assert_eq!(puzzle.goal, Goal::ClearAll);
+        assert_eq!(puzzle.pieces, vec![vec![2, 2, 2], vec![1, 1, 1], vec![5, 5, 5]]);
+        assert_eq!(puzzle.field.block_by_coord(5, 16).color_index(), Some(2));
+        assert_eq!(Puzzle::parse(&puzzle.to_text()).unwrap(), puzzle);
+
+        let result = solve(&puzzle);
+        let solution = result.solution.unwrap();
+        assert_eq!(solution.moves.len(), 2);
+        let steps = replay(&puzzle, &solution.moves).unwrap();
+        assert!(Goal::ClearAll.is_reached(&steps.last().unwrap().field, solution.score));
+
+        let impossible = Puzzle::parse("goal clear-color 4\npieces 555\nfield\n44....\n").unwrap();
+        assert_eq!(solve(&impossible).solution, None);
+    }
+}
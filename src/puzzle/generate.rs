@@ -0,0 +1,223 @@
+//! Procedural generation of puzzles.
+//!
+//! Random start fields, piece lists and goals are generated from a seed and passed to the solver;
+//! only puzzles that can be solved (and are not solved from the start) are kept.
+//!
+//! The difficulty of a puzzle is estimated from its shortest solution as the number of pieces it
+//! needs plus twice the number of additional links in its deepest chain (a solution whose deepest
+//! chain clears sequences three times in succession has two additional links).
+
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::{BLOCK_COLOR_COUNT, FIELD_HEIGHT_BLOCKS, FIELD_WIDTH_BLOCKS};
+use crate::ai::settle_field;
+use crate::game::rng_from_seed;
+use crate::model::{Block, BlockState, Field, FieldBlock};
+use crate::puzzle::{Goal, Puzzle, ReplayStep, replay, solve};
+
+
+/// After how many candidates without a puzzle the progress is reported.
+const PROGRESS_INTERVAL: u32 = 1000;
+
+/// The length of the generated pieces.
+const PIECE_LENGTH: usize = 3;
+
+
+/// The parameters of the generated puzzles.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Settings {
+    /// The largest number of pieces in a puzzle.
+    pub max_pieces: usize,
+
+    /// The height of the highest tower in the start field.
+    pub max_height: u32,
+
+    /// The number of different colors in a puzzle.
+    pub color_count: usize,
+
+    /// Puzzles that are easier than this are discarded.
+    pub min_difficulty: u32,
+
+    /// How many candidates are generated for each puzzle before giving up.
+    pub max_attempts: u32,
+}
+
+
+/// Estimates the difficulty of a puzzle from the steps of its shortest solution.
+pub(crate) fn difficulty(steps: &[ReplayStep]) -> u32 {
+    let deepest_chain = steps.iter()
+        .map(|step| step.chain_depth)
+        .max()
+        .unwrap_or(0);
+    u32::try_from(steps.len()).unwrap() + 2 * deepest_chain.saturating_sub(1)
+}
+
+
+/// Generates a random puzzle, which may or may not be solvable.
+fn generate_candidate(rng: &mut StdRng, settings: &Settings) -> Puzzle {
+    let mut palette: Vec<u8> = (0..u8::try_from(BLOCK_COLOR_COUNT).unwrap()).collect();
+    palette.shuffle(rng);
+    palette.truncate(settings.color_count.clamp(1, BLOCK_COLOR_COUNT));
+
+    // leave space for the pieces to appear
+    let max_height = settings.max_height.min(FIELD_HEIGHT_BLOCKS - u32::try_from(PIECE_LENGTH).unwrap());
+    let mut field = Field::new();
+    for x in 0..FIELD_WIDTH_BLOCKS {
+        let height = rng.gen_range(0..=max_height);
+        for y in (FIELD_HEIGHT_BLOCKS - height)..FIELD_HEIGHT_BLOCKS {
+            *field.block_by_coord_mut(x, y) = FieldBlock::Block(Block {
+                color_index: *palette.choose(rng).unwrap(),
+                state: BlockState::Stationary,
+            });
+        }
+    }
+
+    // the start field should not contain any sequences
+    settle_field(&mut field);
+
+    let piece_count = rng.gen_range(1..=settings.max_pieces.max(1));
+    let pieces: Vec<Vec<u8>> = (0..piece_count)
+        .map(|_| (0..PIECE_LENGTH).map(|_| *palette.choose(rng).unwrap()).collect())
+        .collect();
+
+    let field_colors: Vec<u8> = field.blocks().iter()
+        .filter_map(|b| b.color_index())
+        .collect();
+    let goal = match rng.gen_range(0..3) {
+        0 if !field_colors.is_empty() => Goal::ClearAll,
+        1 if !field_colors.is_empty() => Goal::ClearColor(*field_colors.choose(rng).unwrap()),
+        _ => {
+            let max_target = u64::try_from(PIECE_LENGTH * piece_count).unwrap();
+            Goal::Score(rng.gen_range(1..=max_target))
+        },
+    };
+
+    Puzzle {
+        goal,
+        field,
+        pieces,
+    }
+}
+
+
+/// A puzzle that has been verified to be solvable.
+pub(crate) struct GeneratedPuzzle {
+    pub puzzle: Puzzle,
+    pub steps: Vec<ReplayStep>,
+    pub difficulty: u32,
+}
+
+
+/// Generates candidates until one of them is solvable and difficult enough, reporting the progress
+/// on standard error every now and then.
+///
+/// Returns `None` if no such puzzle was found within the maximum number of attempts.
+pub(crate) fn generate_puzzle(rng: &mut StdRng, settings: &Settings) -> Option<GeneratedPuzzle> {
+    for attempt in 0..settings.max_attempts {
+        if attempt > 0 && attempt % PROGRESS_INTERVAL == 0 {
+            eprintln!("{} of {} candidates tried", attempt, settings.max_attempts);
+        }
+
+        let puzzle = generate_candidate(rng, settings);
+        let solution = match solve(&puzzle).solution {
+            Some(s) => s,
+            None => continue,
+        };
+        if solution.moves.is_empty() {
+            // already solved
+            continue;
+        }
+
+        let steps = replay(&puzzle, &solution.moves)
+            .expect("solution cannot be replayed");
+        let difficulty = difficulty(&steps);
+        if difficulty < settings.min_difficulty {
+            continue;
+        }
+
+        return Some(GeneratedPuzzle {
+            puzzle,
+            steps,
+            difficulty,
+        });
+    }
+    None
+}
+
+
+/// Generates puzzles from the seed and writes them into the directory as `puzzle-NNNN.txt`.
+pub(crate) fn generate_files(seed: u128, count: usize, dir: &Path, settings: &Settings) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut rng = rng_from_seed(seed);
+    for i in 0..count {
+        let generated = generate_puzzle(&mut rng, settings)
+            .ok_or_else(|| io::Error::other(format!(
+                "no puzzle with difficulty {} found in {} attempts",
+                settings.min_difficulty, settings.max_attempts,
+            )))?;
+        let deepest_chain = generated.steps.iter()
+            .map(|step| step.chain_depth)
+            .max()
+            .unwrap_or(0);
+
+        let mut text = String::new();
+        text.push_str(&format!("# generated from seed {}, puzzle {}\n", seed, i));
+        text.push_str(&format!(
+            "# difficulty {}: solvable with {} pieces, deepest chain {}\n",
+            generated.difficulty, generated.steps.len(), deepest_chain,
+        ));
+        text.push_str(&generated.puzzle.to_text());
+
+        let path = dir.join(format!("puzzle-{:04}.txt", i));
+        fs::write(&path, text)?;
+        println!("{}: difficulty {}", path.display(), generated.difficulty);
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Settings, difficulty, generate_puzzle};
+    use crate::game::rng_from_seed;
+    use crate::model::Field;
+    use crate::puzzle::{ReplayStep, solve};
+
+    #[test]
+    fn test_generate() {
+        let step = |chain_depth| ReplayStep {
+            field: Field::new(),
+            score: 0,
+            chain_depth,
+        };
+        assert_eq!(difficulty(&[]), 0);
+        assert_eq!(difficulty(&[step(0), step(1)]), 2);
+        assert_eq!(difficulty(&[step(3), step(0), step(1)]), 3 + 2 * 2);
+
+        let settings = Settings {
+            max_pieces: 2,
+            max_height: 5,
+            color_count: 4,
+            min_difficulty: 2,
+            max_attempts: 1000,
+        };
+        let mut rng = rng_from_seed(1);
+        let generated = generate_puzzle(&mut rng, &settings)
+            .expect("no puzzle generated");
+        assert!(generated.difficulty >= settings.min_difficulty);
+        assert_eq!(generated.difficulty, difficulty(&generated.steps));
+
+        let solution = solve(&generated.puzzle).solution
+            .expect("generated puzzle cannot be solved");
+        assert_eq!(solution.moves.len(), generated.steps.len());
+        assert!(generated.steps.len() <= settings.max_pieces);
+    }
+}
@@ -0,0 +1,368 @@
+//! Recording of decision points for training and analysis.
+//!
+//! Every piece placed during a game becomes one line of JSON (JSONL) in the record file:
+//!
+//! ```text
+//! {"game":0,"move":12,"player":"human","field":"......(108 cells)","piece":[3,0,4],"column":2,"rotation":1,"score_delta":2,"final_score":17,"game_over":true}
+//! ```
+//!
+//! * `game`: the index of the game since the program was started
+//! * `move`: the index of the piece within the game
+//! * `player`: who placed the piece (`human`, `ai` or `bot`)
+//! * `field`: the field before the piece was placed, row by row from the top in the encoding of
+//!   the bot protocol (`.` is an empty cell and a digit is the color index of a block)
+//! * `piece`: the color indexes of the piece from top to bottom as it appeared
+//! * `column` and `rotation`: where the piece was placed and how often it was rotated, as in the
+//!   bot protocol
+//! * `score_delta`: the score gained by placing the piece, including all resulting cascades
+//! * `final_score` and `game_over`: the score at the end of the game and whether the game ended
+//!   because the field overflowed (as opposed to being restarted or quit)
+//!
+//! Since the outcome of a game is only known at its end, the lines of a game are written once it
+//! is over.
+//!
+//! A record file also serves as a replay of its games, as the placements of the pieces determine
+//! everything else that happens.
+
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::ai::BestMove;
+use crate::env::Observation;
+use crate::game::{Game, GameState, Piece};
+use crate::model::{Block, BlockState, Field, FieldBlock};
+use crate::{FIELD_BLOCK_COUNT, FIELD_WIDTH_BLOCKS, NEW_BLOCK_COLUMN};
+
+
+/// Who placed a piece.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Player {
+    Human,
+    Ai,
+    Bot,
+}
+impl Player {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Human => "human",
+            Self::Ai => "ai",
+            Self::Bot => "bot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(Self::Human),
+            "ai" => Some(Self::Ai),
+            "bot" => Some(Self::Bot),
+            _ => None,
+        }
+    }
+}
+
+
+/// A single decision point of a game.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Decision {
+    pub game: u64,
+    pub move_index: u64,
+    pub player: Player,
+
+    /// The field before the piece was placed, in the encoding of [`Observation::field_string`].
+    pub field: String,
+
+    /// The colors of the piece from top to bottom as it appeared.
+    pub piece: Vec<u8>,
+
+    pub best_move: BestMove,
+    pub score_delta: u64,
+    pub final_score: u64,
+    pub game_over: bool,
+}
+impl Decision {
+    /// Returns the field before the piece was placed, with the piece descending at the top.
+    pub fn field_with_piece(&self) -> Field {
+        let mut field = Field::new();
+        for ((x, y), cell) in Field::coords().zip(self.field.chars()) {
+            if let Some(color_index) = cell.to_digit(10) {
+                *field.block_by_coord_mut(x, y) = FieldBlock::Block(Block {
+                    color_index: color_index.try_into().unwrap(),
+                    state: BlockState::Stationary,
+                });
+            }
+        }
+        for (y, &color_index) in self.piece.iter().enumerate() {
+            *field.block_by_coord_mut(NEW_BLOCK_COLUMN, y.try_into().unwrap()) = FieldBlock::Block(Block {
+                color_index,
+                state: BlockState::Descending,
+            });
+        }
+        field
+    }
+
+    pub fn to_json(&self) -> String {
+        let piece_strings: Vec<String> = self.piece.iter()
+            .map(|c| c.to_string())
+            .collect();
+        format!(
+            "{{\"game\":{},\"move\":{},\"player\":\"{}\",\"field\":\"{}\",\"piece\":[{}],\"column\":{},\"rotation\":{},\"score_delta\":{},\"final_score\":{},\"game_over\":{}}}",
+            self.game, self.move_index, self.player.as_str(), self.field, piece_strings.join(","),
+            self.best_move.column, self.best_move.rotate_count, self.score_delta, self.final_score,
+            self.game_over,
+        )
+    }
+
+    /// Parses a line written by [`Decision::to_json`].
+    ///
+    /// This is not a general JSON parser; it only understands the flat objects written by this
+    /// module.
+    pub fn from_json(line: &str) -> Result<Self, String> {
+        let inner = line.trim()
+            .strip_prefix('{').and_then(|l| l.strip_suffix('}'))
+            .ok_or_else(|| format!("not a JSON object: {:?}", line))?;
+
+        let mut game = None;
+        let mut move_index = None;
+        let mut player = None;
+        let mut field = None;
+        let mut piece = None;
+        let mut column = None;
+        let mut rotation = None;
+        let mut score_delta = None;
+        let mut final_score = None;
+        let mut game_over = None;
+
+        let mut rest = inner;
+        while !rest.is_empty() {
+            let (key, after_key) = rest
+                .strip_prefix('"')
+                .and_then(|r| r.split_once("\":"))
+                .ok_or_else(|| format!("invalid key in {:?}", line))?;
+            let value_end = if after_key.starts_with('[') {
+                after_key.find(']').map(|i| i + 1)
+            } else if let Some(quoted) = after_key.strip_prefix('"') {
+                quoted.find('"').map(|i| i + 2)
+            } else {
+                Some(after_key.find(',').unwrap_or(after_key.len()))
+            }.ok_or_else(|| format!("unterminated value in {:?}", line))?;
+            let value = &after_key[..value_end];
+            rest = after_key[value_end..].strip_prefix(',').unwrap_or(&after_key[value_end..]);
+
+            let unquoted = value.trim_matches('"');
+            let invalid = || format!("invalid value for {:?} in {:?}", key, line);
+            match key {
+                "game" => game = Some(unquoted.parse().map_err(|_| invalid())?),
+                "move" => move_index = Some(unquoted.parse().map_err(|_| invalid())?),
+                "player" => player = Some(Player::from_name(unquoted).ok_or_else(invalid)?),
+                "field" => {
+                    if unquoted.chars().count() != FIELD_BLOCK_COUNT {
+                        return Err(invalid());
+                    }
+                    field = Some(unquoted.to_owned());
+                },
+                "piece" => {
+                    let colors: Result<Vec<u8>, _> = value
+                        .trim_start_matches('[').trim_end_matches(']')
+                        .split(',')
+                        .filter(|c| !c.is_empty())
+                        .map(|c| c.parse())
+                        .collect();
+                    piece = Some(colors.map_err(|_| invalid())?);
+                },
+                "column" => {
+                    let c: u32 = unquoted.parse().map_err(|_| invalid())?;
+                    if c >= FIELD_WIDTH_BLOCKS {
+                        return Err(invalid());
+                    }
+                    column = Some(c);
+                },
+                "rotation" => rotation = Some(unquoted.parse().map_err(|_| invalid())?),
+                "score_delta" => score_delta = Some(unquoted.parse().map_err(|_| invalid())?),
+                "final_score" => final_score = Some(unquoted.parse().map_err(|_| invalid())?),
+                "game_over" => game_over = Some(unquoted.parse().map_err(|_| invalid())?),
+                _ => {}, // ignore unknown keys
+            }
+        }
+
+        let missing = |key: &str| format!("missing {:?} in {:?}", key, line);
+        Ok(Self {
+            game: game.ok_or_else(|| missing("game"))?,
+            move_index: move_index.ok_or_else(|| missing("move"))?,
+            player: player.ok_or_else(|| missing("player"))?,
+            field: field.ok_or_else(|| missing("field"))?,
+            piece: piece.ok_or_else(|| missing("piece"))?,
+            best_move: BestMove {
+                column: column.ok_or_else(|| missing("column"))?,
+                rotate_count: rotation.ok_or_else(|| missing("rotation"))?,
+            },
+            score_delta: score_delta.ok_or_else(|| missing("score_delta"))?,
+            final_score: final_score.ok_or_else(|| missing("final_score"))?,
+            game_over: game_over.ok_or_else(|| missing("game_over"))?,
+        })
+    }
+}
+
+
+/// Reads all decisions from a record file.
+pub(crate) fn read_decisions(path: &Path) -> io::Result<Vec<Decision>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut decisions = Vec::new();
+    for line_res in reader.lines() {
+        let line = line_res?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let decision = Decision::from_json(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        decisions.push(decision);
+    }
+    Ok(decisions)
+}
+
+
+/// The decision about the current piece.
+struct PendingDecision {
+    player: Player,
+    field: String,
+    spawned_piece: Piece,
+    score_before: u64,
+
+    /// Where the piece was placed, once it has been.
+    placed_piece: Option<Piece>,
+}
+
+
+/// Writes the decision points of games to a record file.
+pub(crate) struct Recorder {
+    writer: BufWriter<File>,
+    game_index: u64,
+    pending: Option<PendingDecision>,
+    decisions: Vec<Decision>,
+}
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            game_index: 0,
+            pending: None,
+            decisions: Vec::new(),
+        })
+    }
+
+    /// Notes where the current piece has been placed. Must be called after every frame, since the
+    /// game forgets the placement once the next piece appears.
+    pub fn update(&mut self, game: &Game) {
+        if let Some(pending) = self.pending.as_mut() {
+            if pending.placed_piece.is_none() {
+                pending.placed_piece = game.placed_piece.clone();
+            }
+        }
+    }
+
+    /// Completes the decision about the previous piece, if it has been placed.
+    fn complete_pending(&mut self, game: &Game) {
+        self.update(game);
+        let pending = match self.pending.take() {
+            Some(p) => p,
+            None => return,
+        };
+        let placed_piece = match &pending.placed_piece {
+            Some(p) => p,
+            None => return,
+        };
+        let rotate_count = match pending.spawned_piece.rotations_to(placed_piece) {
+            Some(rc) => rc,
+            None => return,
+        };
+
+        self.decisions.push(Decision {
+            game: self.game_index,
+            move_index: self.decisions.len().try_into().unwrap(),
+            player: pending.player,
+            field: pending.field,
+            piece: pending.spawned_piece.colors,
+            best_move: BestMove {
+                column: placed_piece.column,
+                rotate_count,
+            },
+            score_delta: game.score - pending.score_before,
+            final_score: 0,
+            game_over: false,
+        });
+    }
+
+    /// Records that a new piece has appeared and will be placed by the given player.
+    ///
+    /// Must be called before the piece is moved.
+    pub fn piece_spawned(&mut self, game: &Game, player: Player) {
+        self.complete_pending(game);
+
+        if let Some(spawned_piece) = &game.spawned_piece {
+            self.pending = Some(PendingDecision {
+                player,
+                field: Observation::from_field(&game.field).field_string(),
+                spawned_piece: spawned_piece.clone(),
+                score_before: game.score,
+                placed_piece: None,
+            });
+        }
+    }
+
+    /// Writes out the decisions of the current game, which has just ended, been restarted or quit.
+    pub fn game_ended(&mut self, game: &Game) -> io::Result<()> {
+        self.complete_pending(game);
+        if self.decisions.is_empty() {
+            // nothing happened; don't count this as a game
+            return Ok(());
+        }
+
+        let game_over = game.state == GameState::Over;
+        for mut decision in self.decisions.drain(..) {
+            decision.final_score = game.score;
+            decision.game_over = game_over;
+            writeln!(self.writer, "{}", decision.to_json())?;
+        }
+        self.writer.flush()?;
+
+        self.game_index += 1;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Decision, Player};
+    use crate::ai::BestMove;
+
+    #[test]
+    fn test_decision_json_round_trip() {
+        let mut field = ".".repeat(6*17);
+        field.push_str("01.5..");
+        let decision = Decision {
+            game: 1,
+            move_index: 12,
+            player: Player::Bot,
+            field,
+            piece: vec![3, 0, 4],
+            best_move: BestMove {
+                column: 2,
+                rotate_count: 1,
+            },
+            score_delta: 2,
+            final_score: 17,
+            game_over: true,
+        };
+        let json = decision.to_json();
+        assert!(json.starts_with("{\"game\":1,\"move\":12,\"player\":\"bot\",\"field\":\"......"));
+        assert!(json.ends_with("01.5..\",\"piece\":[3,0,4],\"column\":2,\"rotation\":1,\"score_delta\":2,\"final_score\":17,\"game_over\":true}"));
+        assert_eq!(Decision::from_json(&json).unwrap(), decision);
+
+        let field_with_piece = decision.field_with_piece();
+        assert_eq!(field_with_piece.block_by_coord(1, 17).color_index(), Some(1));
+        assert_eq!(field_with_piece.block_by_coord(3, 1).color_index(), Some(0));
+        assert!(field_with_piece.block_by_coord(3, 1).as_block().unwrap().state.is_descending());
+    }
+}
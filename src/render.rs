@@ -0,0 +1,94 @@
+//! Drawing primitives that the board and the HUD are drawn with, independent of the backend.
+
+
+#[cfg(feature = "sdl")]
+pub(crate) mod sdl;
+pub(crate) mod scaled;
+pub(crate) mod software;
+
+
+/// A color with an alpha channel.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+impl Rgba {
+    pub const BLACK: Self = Self::rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Self = Self::rgb(0xFF, 0xFF, 0xFF);
+    pub const GRAY: Self = Self::rgb(0x80, 0x80, 0x80);
+    pub const RED: Self = Self::rgb(0xFF, 0x00, 0x00);
+    pub const GREEN: Self = Self::rgb(0x00, 0xFF, 0x00);
+    pub const BLUE: Self = Self::rgb(0x00, 0x00, 0xFF);
+    pub const YELLOW: Self = Self::rgb(0xFF, 0xFF, 0x00);
+    pub const CYAN: Self = Self::rgb(0x00, 0xFF, 0xFF);
+    pub const MAGENTA: Self = Self::rgb(0xFF, 0x00, 0xFF);
+
+    /// Returns an opaque color.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::new(r, g, b, 0xFF)
+    }
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self {
+            r,
+            g,
+            b,
+            a,
+        }
+    }
+
+    /// Returns the color with a different alpha channel.
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self::new(self.r, self.g, self.b, a)
+    }
+}
+impl From<(u8, u8, u8)> for Rgba {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        Self::rgb(r, g, b)
+    }
+}
+
+
+/// Something the game can be drawn on.
+///
+/// Colors with an alpha channel below 0xFF are blended with what has already been drawn.
+pub(crate) trait Renderer {
+    /// An image that can be drawn repeatedly, such as a block.
+    type Sprite;
+
+    /// Returns the width and height of the drawing area in pixels.
+    fn output_size(&self) -> (u32, u32);
+
+    /// Creates a sprite from its pixels, row by row from the top.
+    fn create_sprite(&mut self, width: u32, height: u32, pixels: &[Rgba]) -> Self::Sprite;
+
+    /// Fills the whole drawing area with the color.
+    fn clear(&mut self, color: Rgba);
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba);
+
+    /// Draws a line one pixel wide, including both end points.
+    fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Rgba);
+
+    /// Draws the sprite scaled into the rectangle.
+    fn blit_sprite(&mut self, sprite: &Self::Sprite, x: i32, y: i32, width: u32, height: u32);
+
+    /// Shows what has been drawn since the previous call.
+    fn present(&mut self);
+
+    /// Draws the outline of a rectangle one pixel wide.
+    fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let right = x + i32::try_from(width).unwrap() - 1;
+        let bottom = y + i32::try_from(height).unwrap() - 1;
+        self.draw_line((x, y), (right, y), color);
+        self.draw_line((x, bottom), (right, bottom), color);
+        self.draw_line((x, y), (x, bottom), color);
+        self.draw_line((right, y), (right, bottom), color);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::render::{Renderer, Rgba};
+
+
+/// How drawing in coordinates for a drawing area of a fixed logical size maps onto an actual
+/// drawing area of any size.
+///
+/// The logical area is scaled uniformly so that it fills as much of the actual area as possible
+/// and is centered, leaving black bars along the sides that are too long.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Layout {
+    logical_size: (u32, u32),
+    scale_numerator: u32,
+    scale_denominator: u32,
+    offset_x: i32,
+    offset_y: i32,
+}
+impl Layout {
+    /// Calculates the layout of the logical area within the actual area. If `integer_scaling` is
+    /// set, the logical area is only scaled by whole multiples, which keeps the pixels sharp.
+    pub fn new(logical_size: (u32, u32), actual_size: (u32, u32), integer_scaling: bool) -> Self {
+        let (logical_width, logical_height) = logical_size;
+        let (actual_width, actual_height) = actual_size;
+
+        // the scale is limited by the dimension in which the actual area is relatively smaller
+        let (mut scale_numerator, mut scale_denominator) = if u64::from(actual_width) * u64::from(logical_height) <= u64::from(actual_height) * u64::from(logical_width) {
+            (actual_width, logical_width)
+        } else {
+            (actual_height, logical_height)
+        };
+        if integer_scaling {
+            scale_numerator = (scale_numerator / scale_denominator).max(1);
+            scale_denominator = 1;
+        }
+
+        let mut layout = Self {
+            logical_size,
+            scale_numerator,
+            scale_denominator,
+            offset_x: 0,
+            offset_y: 0,
+        };
+        let scaled_width = i64::from(layout.scale(logical_width.try_into().unwrap()));
+        let scaled_height = i64::from(layout.scale(logical_height.try_into().unwrap()));
+        layout.offset_x = ((i64::from(actual_width) - scaled_width) / 2).try_into().unwrap();
+        layout.offset_y = ((i64::from(actual_height) - scaled_height) / 2).try_into().unwrap();
+        layout
+    }
+
+    fn scale(&self, value: i32) -> i32 {
+        (i64::from(value) * i64::from(self.scale_numerator))
+            .div_euclid(i64::from(self.scale_denominator))
+            .try_into().unwrap()
+    }
+
+    /// Returns the layout with everything moved by the logical distances.
+    pub fn shifted(self, x: i32, y: i32) -> Self {
+        Self {
+            offset_x: self.offset_x + self.scale(x),
+            offset_y: self.offset_y + self.scale(y),
+            ..self
+        }
+    }
+
+    /// Converts logical coordinates into actual coordinates.
+    pub fn point(&self, x: i32, y: i32) -> (i32, i32) {
+        (self.offset_x + self.scale(x), self.offset_y + self.scale(y))
+    }
+
+    /// Converts a logical rectangle into an actual one.
+    ///
+    /// Rectangles that touch in logical coordinates also touch in actual coordinates, which means
+    /// that small rectangles may shrink to nothing when the area is scaled down.
+    pub fn rect(&self, x: i32, y: i32, width: u32, height: u32) -> (i32, i32, u32, u32) {
+        let (left, top) = self.point(x, y);
+        let (right, bottom) = self.point(
+            x + i32::try_from(width).unwrap(),
+            y + i32::try_from(height).unwrap(),
+        );
+        (left, top, (right - left).try_into().unwrap(), (bottom - top).try_into().unwrap())
+    }
+
+    /// Converts a logical length into an actual one of at least one pixel.
+    pub fn length(&self, length: u32) -> u32 {
+        u32::try_from(self.scale(length.try_into().unwrap())).unwrap().max(1)
+    }
+}
+
+
+/// Draws in logical coordinates onto another renderer as described by a layout.
+pub(crate) struct ScaledRenderer<'r, R: Renderer> {
+    inner: &'r mut R,
+    layout: Layout,
+}
+impl<'r, R: Renderer> ScaledRenderer<'r, R> {
+    pub fn new(inner: &'r mut R, layout: Layout) -> Self {
+        Self {
+            inner,
+            layout,
+        }
+    }
+}
+impl<'r, R: Renderer> Renderer for ScaledRenderer<'r, R> {
+    type Sprite = R::Sprite;
+
+    fn output_size(&self) -> (u32, u32) {
+        self.layout.logical_size
+    }
+
+    fn create_sprite(&mut self, width: u32, height: u32, pixels: &[Rgba]) -> Self::Sprite {
+        self.inner.create_sprite(width, height, pixels)
+    }
+
+    fn clear(&mut self, color: Rgba) {
+        // including the bars around the logical area
+        self.inner.clear(color);
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba) {
+        let (x, y, width, height) = self.layout.rect(x, y, width, height);
+        if width > 0 && height > 0 {
+            self.inner.fill_rect(x, y, width, height, color);
+        }
+    }
+
+    fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Rgba) {
+        let from = self.layout.point(from.0, from.1);
+        let to = self.layout.point(to.0, to.1);
+        self.inner.draw_line(from, to, color);
+    }
+
+    fn blit_sprite(&mut self, sprite: &Self::Sprite, x: i32, y: i32, width: u32, height: u32) {
+        let (x, y, width, height) = self.layout.rect(x, y, width, height);
+        if width > 0 && height > 0 {
+            self.inner.blit_sprite(sprite, x, y, width, height);
+        }
+    }
+
+    fn present(&mut self) {
+        self.inner.present();
+    }
+
+    fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba) {
+        let (x, y, width, height) = self.layout.rect(x, y, width, height);
+        self.inner.draw_rect(x, y, width, height, color);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Layout;
+
+    #[test]
+    fn test_layout() {
+        let unscaled = Layout::new((800, 600), (800, 600), false);
+        assert_eq!(unscaled.rect(325, 50, 25, 25), (325, 50, 25, 25));
+
+        // a 1080p screen is limited by its height and gets bars on the left and right
+        let full_hd = Layout::new((800, 600), (1920, 1080), false);
+        assert_eq!(full_hd.point(0, 0), (240, 0));
+        assert_eq!(full_hd.point(800, 600), (1680, 1080));
+        assert_eq!(full_hd.length(25), 45);
+
+        // neighboring rectangles stay neighbors even if the scale is not a whole number
+        let odd = Layout::new((800, 600), (1000, 1000), false);
+        let (left_x, _, left_width, _) = odd.rect(325, 50, 25, 25);
+        let (right_x, _, _, _) = odd.rect(350, 50, 25, 25);
+        assert_eq!(left_x + i32::try_from(left_width).unwrap(), right_x);
+
+        let integer = Layout::new((800, 600), (1920, 1080), true);
+        assert_eq!(integer.point(0, 0), (560, 240));
+        assert_eq!(integer.length(25), 25);
+        let integer_4k = Layout::new((800, 600), (3840, 2160), true);
+        assert_eq!(integer_4k.point(0, 0), (720, 180));
+        assert_eq!(integer_4k.length(25), 75);
+    }
+}
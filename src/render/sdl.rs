@@ -0,0 +1,91 @@
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureAccess, TextureCreator};
+use sdl2::video::Window;
+
+use crate::render::{Renderer, Rgba};
+
+
+impl From<Rgba> for Color {
+    fn from(color: Rgba) -> Self {
+        Color::RGBA(color.r, color.g, color.b, color.a)
+    }
+}
+
+
+/// Draws using an SDL canvas, which may belong to a window or to a surface in memory.
+pub(crate) struct SdlRenderer<'a, T: RenderTarget> {
+    canvas: Canvas<T>,
+    texture_creator: &'a TextureCreator<T::Context>,
+}
+impl<'a, T: RenderTarget> SdlRenderer<'a, T> {
+    pub fn new(mut canvas: Canvas<T>, texture_creator: &'a TextureCreator<T::Context>) -> Self {
+        canvas.set_blend_mode(BlendMode::Blend);
+        Self {
+            canvas,
+            texture_creator,
+        }
+    }
+}
+impl<'a> SdlRenderer<'a, Window> {
+    pub fn window_mut(&mut self) -> &mut Window {
+        self.canvas.window_mut()
+    }
+}
+impl<'a, T: RenderTarget> Renderer for SdlRenderer<'a, T> {
+    type Sprite = Texture<'a>;
+
+    fn output_size(&self) -> (u32, u32) {
+        self.canvas.output_size().unwrap()
+    }
+
+    fn create_sprite(&mut self, width: u32, height: u32, pixels: &[Rgba]) -> Self::Sprite {
+        let mut data = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels {
+            data.push(pixel.r);
+            data.push(pixel.g);
+            data.push(pixel.b);
+            data.push(pixel.a);
+        }
+
+        let mut texture = self.texture_creator.create_texture(
+            Some(PixelFormatEnum::ABGR8888),
+            TextureAccess::Static,
+            width, height,
+        ).unwrap();
+        texture.update(
+            Rect::new(0, 0, width, height),
+            &data,
+            (width * 4).try_into().unwrap(),
+        ).unwrap();
+        texture
+    }
+
+    fn clear(&mut self, color: Rgba) {
+        self.canvas.set_draw_color(color);
+        self.canvas.clear();
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba) {
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rect(Rect::new(x, y, width, height)).unwrap();
+    }
+
+    fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Rgba) {
+        self.canvas.set_draw_color(color);
+        self.canvas.draw_line(from, to).unwrap();
+    }
+
+    fn blit_sprite(&mut self, sprite: &Self::Sprite, x: i32, y: i32, width: u32, height: u32) {
+        self.canvas.copy(sprite, None, Rect::new(x, y, width, height)).unwrap();
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba) {
+        self.canvas.set_draw_color(color);
+        self.canvas.draw_rect(Rect::new(x, y, width, height)).unwrap();
+    }
+}
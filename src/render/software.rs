@@ -0,0 +1,410 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::render::{Renderer, Rgba};
+
+
+fn has_bmp_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("bmp"))
+        .unwrap_or(false)
+}
+
+
+/// A picture in memory.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+
+    /// The pixels row by row from the top.
+    pub pixels: Vec<Rgba>,
+}
+impl Image {
+    pub fn new(width: u32, height: u32, color: Rgba) -> Self {
+        let pixel_count: usize = (width * height).try_into().unwrap();
+        Self {
+            width,
+            height,
+            pixels: vec![color; pixel_count],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x).try_into().unwrap()
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Rgba {
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Draws the color over the pixel, blending it according to its alpha channel. Pixels outside
+    /// the image are ignored.
+    fn blend_pixel(&mut self, x: i32, y: i32, color: Rgba) {
+        let (x, y) = match (u32::try_from(x), u32::try_from(y)) {
+            (Ok(x), Ok(y)) if x < self.width && y < self.height => (x, y),
+            _ => return,
+        };
+        let index = self.index(x, y);
+        let below = self.pixels[index];
+        let blend = |top: u8, bottom: u8| -> u8 {
+            let top = u16::from(top);
+            let bottom = u16::from(bottom);
+            let alpha = u16::from(color.a);
+            ((top * alpha + bottom * (0xFF - alpha)) / 0xFF).try_into().unwrap()
+        };
+        self.pixels[index] = Rgba::new(
+            blend(color.r, below.r),
+            blend(color.g, below.g),
+            blend(color.b, below.b),
+            below.a.max(color.a),
+        );
+    }
+
+    /// Reads an uncompressed Windows bitmap with 24 or 32 bits per pixel.
+    pub fn read_bmp<R: Read>(mut reader: R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() < 14 + 40 || &data[0..2] != b"BM" {
+            return Err(invalid("not a bitmap"));
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes(data[offset..offset+2].try_into().unwrap());
+        let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset+4].try_into().unwrap());
+        let i32_at = |offset: usize| i32::from_le_bytes(data[offset..offset+4].try_into().unwrap());
+
+        let pixel_offset: usize = u32_at(10).try_into().unwrap();
+        let width = u32::try_from(i32_at(18))
+            .map_err(|_| invalid("negative bitmap width"))?;
+        let raw_height = i32_at(22);
+        let bits_per_pixel = u16_at(28);
+        let compression = u32_at(30);
+
+        // 32-bit bitmaps may declare their (standard) channel masks as bit fields
+        let bytes_per_pixel: u32 = match (bits_per_pixel, compression) {
+            (24, 0) => 3,
+            (32, 0|3) => 4,
+            _ => return Err(invalid("only uncompressed bitmaps with 24 or 32 bits per pixel are supported")),
+        };
+
+        // without an alpha mask (which only the larger info headers can declare), the fourth byte
+        // of each pixel is unused
+        let header_size = u32_at(14);
+        let has_alpha = compression == 3
+            && header_size >= 56
+            && data.len() >= 14 + 56
+            && u32_at(14 + 52) != 0;
+
+        // rows are stored from the bottom unless the height is negative
+        let height = raw_height.unsigned_abs();
+        let bottom_up = raw_height > 0;
+        let too_large = || invalid("bitmap is too large");
+        let row_size = width.checked_mul(bytes_per_pixel)
+            .and_then(|row_bytes| row_bytes.checked_next_multiple_of(4))
+            .and_then(|row_size| usize::try_from(row_size).ok())
+            .ok_or_else(too_large)?;
+        let pixel_count = width.checked_mul(height)
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(too_large)?;
+        let data_end = usize::try_from(height).ok()
+            .and_then(|height| row_size.checked_mul(height))
+            .and_then(|data_size| pixel_offset.checked_add(data_size))
+            .ok_or_else(too_large)?;
+        if data.len() < data_end {
+            return Err(invalid("bitmap data is truncated"));
+        }
+
+        let mut pixels = Vec::with_capacity(pixel_count);
+        for y in 0..height {
+            let stored_row = if bottom_up { height - 1 - y } else { y };
+            let row_start = pixel_offset + row_size * usize::try_from(stored_row).unwrap();
+            for x in 0..width {
+                let start = row_start + usize::try_from(x * bytes_per_pixel).unwrap();
+                let alpha = if has_alpha { data[start + 3] } else { 0xFF };
+                pixels.push(Rgba::new(data[start + 2], data[start + 1], data[start], alpha));
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Reads a PNG of any color type.
+    pub fn read_png<R: Read>(reader: R) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut png_reader = decoder.read_info()
+            .map_err(io::Error::other)?;
+        let mut data = vec![0; png_reader.output_buffer_size()];
+        let info = png_reader.next_frame(&mut data)
+            .map_err(io::Error::other)?;
+        data.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => data.iter()
+                .map(|&v| Rgba::rgb(v, v, v))
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data.chunks_exact(2)
+                .map(|c| Rgba::new(c[0], c[0], c[0], c[1]))
+                .collect(),
+            png::ColorType::Rgb => data.chunks_exact(3)
+                .map(|c| Rgba::rgb(c[0], c[1], c[2]))
+                .collect(),
+            png::ColorType::Rgba => data.chunks_exact(4)
+                .map(|c| Rgba::new(c[0], c[1], c[2], c[3]))
+                .collect(),
+            png::ColorType::Indexed => unreachable!("indexed colors are expanded"),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Reads an image from a file, as a bitmap if its extension is `.bmp` and as a PNG otherwise.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        if has_bmp_extension(path) {
+            Self::read_bmp(reader)
+        } else {
+            Self::read_png(reader)
+        }
+    }
+
+    /// Returns the part of the image within the rectangle.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let mut pixels = Vec::with_capacity((width * height).try_into().unwrap());
+        for row in y..y+height {
+            let start = self.index(x, row);
+            pixels.extend_from_slice(&self.pixels[start..start + usize::try_from(width).unwrap()]);
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Writes the image as an uncompressed 24-bit Windows bitmap.
+    pub fn write_bmp<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        const HEADER_SIZE: u32 = 14 + 40;
+
+        // each row is padded to a multiple of four bytes
+        let row_size = (self.width * 3).div_ceil(4) * 4;
+        let data_size = row_size * self.height;
+        let width: i32 = self.width.try_into().unwrap();
+        let height: i32 = self.height.try_into().unwrap();
+
+        // file header
+        writer.write_all(b"BM")?;
+        writer.write_all(&(HEADER_SIZE + data_size).to_le_bytes())?;
+        writer.write_all(&[0; 4])?;
+        writer.write_all(&HEADER_SIZE.to_le_bytes())?;
+
+        // info header
+        writer.write_all(&40u32.to_le_bytes())?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // planes
+        writer.write_all(&24u16.to_le_bytes())?; // bits per pixel
+        writer.write_all(&0u32.to_le_bytes())?; // no compression
+        writer.write_all(&data_size.to_le_bytes())?;
+        writer.write_all(&2835i32.to_le_bytes())?; // 72 DPI horizontally
+        writer.write_all(&2835i32.to_le_bytes())?; // and vertically
+        writer.write_all(&0u32.to_le_bytes())?; // no palette
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        // rows from the bottom, in BGR order
+        let padding = vec![0u8; (row_size - self.width * 3).try_into().unwrap()];
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let pixel = self.pixel(x, y);
+                writer.write_all(&[pixel.b, pixel.g, pixel.r])?;
+            }
+            writer.write_all(&padding)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the image as an RGB PNG.
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut png_writer = encoder.write_header()
+            .map_err(io::Error::other)?;
+
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            data.push(pixel.r);
+            data.push(pixel.g);
+            data.push(pixel.b);
+        }
+        png_writer.write_image_data(&data)
+            .map_err(io::Error::other)?;
+        png_writer.finish()
+            .map_err(io::Error::other)
+    }
+
+    /// Writes the image into a file, as a bitmap if its extension is `.bmp` and as a PNG
+    /// otherwise.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if has_bmp_extension(path) {
+            self.write_bmp(&mut writer)?;
+        } else {
+            self.write_png(&mut writer)?;
+        }
+        writer.flush()
+    }
+}
+
+
+/// Draws into an image in memory, without needing a display.
+pub(crate) struct SoftwareRenderer {
+    image: Image,
+}
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            image: Image::new(width, height, Rgba::BLACK),
+        }
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+}
+impl Renderer for SoftwareRenderer {
+    type Sprite = Image;
+
+    fn output_size(&self) -> (u32, u32) {
+        (self.image.width, self.image.height)
+    }
+
+    fn create_sprite(&mut self, width: u32, height: u32, pixels: &[Rgba]) -> Self::Sprite {
+        Image {
+            width,
+            height,
+            pixels: pixels.to_vec(),
+        }
+    }
+
+    fn clear(&mut self, color: Rgba) {
+        for pixel in &mut self.image.pixels {
+            *pixel = color;
+        }
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgba) {
+        for dy in 0..i32::try_from(height).unwrap() {
+            for dx in 0..i32::try_from(width).unwrap() {
+                self.image.blend_pixel(x + dx, y + dy, color);
+            }
+        }
+    }
+
+    fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Rgba) {
+        // Bresenham's algorithm
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let step_x = if x < to.0 { 1 } else { -1 };
+        let step_y = if y < to.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.image.blend_pixel(x, y, color);
+            if (x, y) == to {
+                break;
+            }
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    fn blit_sprite(&mut self, sprite: &Self::Sprite, x: i32, y: i32, width: u32, height: u32) {
+        // scale using the nearest pixel
+        for target_y in 0..height {
+            let source_y = target_y * sprite.height / height;
+            for target_x in 0..width {
+                let source_x = target_x * sprite.width / width;
+                self.image.blend_pixel(
+                    x + i32::try_from(target_x).unwrap(),
+                    y + i32::try_from(target_y).unwrap(),
+                    sprite.pixel(source_x, source_y),
+                );
+            }
+        }
+    }
+
+    fn present(&mut self) {
+        // the image is always up to date
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::Image;
+    use crate::render::Rgba;
+
+    /// Returns a bitmap with an info header of the given size and a single row of 32-bit pixels.
+    fn bitmap(header_size: u32, width: i32, compression: u32, alpha_mask: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let pixel_offset = 14 + header_size;
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BM");
+        data.extend_from_slice(&(pixel_offset + 4 * u32::try_from(pixels.len()).unwrap()).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&pixel_offset.to_le_bytes());
+
+        data.extend_from_slice(&header_size.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&1i32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&32u16.to_le_bytes());
+        data.extend_from_slice(&compression.to_le_bytes());
+        data.extend_from_slice(&[0; 20]);
+        for mask in [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, alpha_mask] {
+            data.extend_from_slice(&u32::to_le_bytes(mask));
+        }
+        data.resize(usize::try_from(pixel_offset).unwrap(), 0);
+
+        for pixel in pixels {
+            data.extend_from_slice(pixel);
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_bmp() {
+        // BGRA order; without an alpha mask the last byte is padding
+        let pixels = [[0x30, 0x20, 0x10, 0x00], [0x60, 0x50, 0x40, 0x80]];
+        let image = Image::read_bmp(&bitmap(40, 2, 0, 0, &pixels)[..]).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixel(0, 0), Rgba::new(0x10, 0x20, 0x30, 0xFF));
+        assert_eq!(image.pixel(1, 0), Rgba::new(0x40, 0x50, 0x60, 0xFF));
+
+        let image = Image::read_bmp(&bitmap(108, 2, 3, 0xFF00_0000, &pixels)[..]).unwrap();
+        assert_eq!(image.pixel(0, 0), Rgba::new(0x10, 0x20, 0x30, 0x00));
+        assert_eq!(image.pixel(1, 0), Rgba::new(0x40, 0x50, 0x60, 0x80));
+
+        let error = Image::read_bmp(&bitmap(40, i32::MAX, 0, 0, &pixels)[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = Image::read_bmp(&bitmap(40, 3, 0, 0, &pixels)[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,176 @@
+//! Drawing game states into image files, both from a running game and from the command line.
+
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{BLOCK_HEIGHT_PX, BLOCK_WIDTH_PX, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::effects::Effects;
+use crate::game::{Game, GameEvent};
+use crate::model::Field;
+use crate::motion::Motion;
+use crate::puzzle::Puzzle;
+use crate::record::read_decisions;
+use crate::render::Renderer;
+use crate::render::scaled::{Layout, ScaledRenderer};
+use crate::render::software::{Image, SoftwareRenderer};
+use crate::theme::ThemeSprites;
+
+
+/// Where the game state to render comes from.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Source {
+    /// A new game with the seed after the AI has placed this many pieces.
+    NewGame { seed: u128, pieces: u64 },
+
+    /// The given move of the given game in a record file, as the piece appeared.
+    Record { path: PathBuf, game: u64, move_index: u64 },
+
+    /// The start field of a puzzle.
+    Puzzle { path: PathBuf },
+}
+
+
+/// Draws the game the same way as the window does and returns the picture.
+pub(crate) fn render(game: &Game, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>) -> Image {
+    let mut motion = Motion::new();
+    motion.update(game);
+    let sprites = scaled_sprites((WINDOW_WIDTH, WINDOW_HEIGHT));
+    render_scaled(game, &motion, &Effects::new(&[]), ghost_blocks, hints_remaining, &sprites, (WINDOW_WIDTH, WINDOW_HEIGHT))
+}
+
+
+/// Creates the sprites for drawing into pictures of the given size (width, height).
+pub(crate) fn scaled_sprites((width, height): (u32, u32)) -> ThemeSprites<Image> {
+    let mut renderer = SoftwareRenderer::new(width, height);
+    let layout = Layout::new((WINDOW_WIDTH, WINDOW_HEIGHT), renderer.output_size(), false);
+    ThemeSprites::new(&mut renderer, crate::theme::current(), layout.length(BLOCK_WIDTH_PX), layout.length(BLOCK_HEIGHT_PX))
+}
+
+
+/// Draws the game the same way as a window of the given size (width, height) does and returns the
+/// picture. The sprites must have been created by [`scaled_sprites`] for the same size.
+pub(crate) fn render_scaled(game: &Game, motion: &Motion, effects: &Effects, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>, sprites: &ThemeSprites<Image>, (width, height): (u32, u32)) -> Image {
+    let mut renderer = SoftwareRenderer::new(width, height);
+    let layout = Layout::new((WINDOW_WIDTH, WINDOW_HEIGHT), renderer.output_size(), false);
+    let (shake_x, shake_y) = effects.shake_offset();
+    crate::draw(&mut ScaledRenderer::new(&mut renderer, layout.shifted(shake_x, shake_y)), game, motion, effects, ghost_blocks, hints_remaining, sprites);
+    renderer.image().clone()
+}
+
+
+/// Saves a picture of the game into a new PNG file in the current directory and returns its path.
+pub(crate) fn save_screenshot(game: &Game, ghost_blocks: &[(u32, u32, u8)], hints_remaining: Option<u32>) -> io::Result<PathBuf> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = PathBuf::from(format!("columns-{}.png", millis));
+    render(game, ghost_blocks, hints_remaining).save(&path)?;
+    Ok(path)
+}
+
+
+fn game_with_field(field: Field, score: u64) -> Game {
+    let mut game = Game::new(0);
+    game.field = field;
+    game.score = score;
+    game
+}
+
+
+/// Reconstructs the game state from the source.
+fn load_game(source: &Source) -> io::Result<Game> {
+    match source {
+        Source::NewGame { seed, pieces } => {
+            let mut game = Game::new(*seed);
+            for placed in 0..=*pieces {
+                if game.run_until_next_piece() != Some(GameEvent::PieceSpawned) || placed == *pieces {
+                    break;
+                }
+                if let Some(best_move) = crate::ai::pick_best_move(&game.field, 1) {
+                    game.apply_move(&best_move);
+                }
+                game.drop_piece();
+            }
+            Ok(game)
+        },
+        Source::Record { path, game, move_index } => {
+            let decisions: Vec<_> = read_decisions(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("failed to read {}: {}", path.display(), e)))?
+                .into_iter()
+                .filter(|d| d.game == *game)
+                .collect();
+            let decision = decisions.iter()
+                .find(|d| d.move_index == *move_index)
+                .ok_or_else(|| io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("move {} of game {} not found in record file", move_index, game),
+                ))?;
+            let score = decisions.iter()
+                .filter(|d| d.move_index < *move_index)
+                .map(|d| d.score_delta)
+                .sum();
+            Ok(game_with_field(decision.field_with_piece(), score))
+        },
+        Source::Puzzle { path } => {
+            let puzzle = Puzzle::read(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("failed to read {}: {}", path.display(), e)))?;
+            Ok(game_with_field(puzzle.field, 0))
+        },
+    }
+}
+
+
+/// Draws the game state from the source into the image file.
+pub(crate) fn render_file(source: &Source, output: &Path) -> io::Result<()> {
+    let game = load_game(source)?;
+    render(&game, &[], None).save(output)
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to write {}: {}", output.display(), e)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::render;
+    use crate::{BLOCK_COLORS, FIELD_OFFSET_LEFT_PX, FIELD_OFFSET_TOP_PX, OPTS, Opts};
+    use crate::game::{Game, GameState};
+    use crate::model::{Block, BlockState, FieldBlock};
+    use crate::render::Rgba;
+
+    #[test]
+    fn test_render() {
+        OPTS.get_or_init(|| Opts::parse_from(["columns"]));
+
+        let mut game = Game::new(0);
+        *game.field.block_by_coord_mut(0, 17) = FieldBlock::Block(Block {
+            color_index: 2,
+            state: BlockState::Stationary,
+        });
+        game.state = GameState::Pause;
+        let image = render(&game, &[], None);
+        assert_eq!((image.width, image.height), (800, 600));
+
+        // the frame around the field
+        let field_left: u32 = FIELD_OFFSET_LEFT_PX.try_into().unwrap();
+        let field_top: u32 = FIELD_OFFSET_TOP_PX.try_into().unwrap();
+        assert_eq!(image.pixel(field_left - 2, field_top + 10), Rgba::rgb(0xC0, 0xC0, 0xC0));
+        assert_eq!(image.pixel(field_left - 1, field_top + 10), Rgba::BLACK);
+
+        // the top left corner of the block is its full color, its center is darker
+        let block_top = field_top + 17 * 25;
+        assert_eq!(image.pixel(field_left, block_top), BLOCK_COLORS[2]);
+        assert_eq!(image.pixel(field_left + 12, block_top + 12), Rgba::rgb(0x00, 0x00, 0xAA));
+
+        // the pause bars are blended over the background
+        assert_eq!(image.pixel(400 - 127 + 10, 300), Rgba::rgb(0x66, 0x66, 0x66));
+
+        let mut bmp = Vec::new();
+        image.write_bmp(&mut bmp).unwrap();
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(bmp.len(), 14 + 40 + 800 * 600 * 3);
+    }
+}
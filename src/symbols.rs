@@ -0,0 +1,104 @@
+//! Symbols that tell the block colors apart by shape, for players who have trouble distinguishing
+//! the colors themselves.
+
+
+use crate::BLOCK_COLOR_COUNT;
+use crate::render::Rgba;
+
+
+/// A shape drawn onto the blocks of one color.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Symbol {
+    Circle,
+    Triangle,
+    Cross,
+    Square,
+    Diamond,
+    Plus,
+}
+impl Symbol {
+    /// Returns whether the symbol covers the point, given in coordinates from -1 to 1 in both
+    /// directions with the origin in the middle of the block.
+    fn covers(self, x: f64, y: f64) -> bool {
+        match self {
+            Self::Circle => x*x + y*y <= 0.7*0.7,
+            Self::Triangle => (-0.65..=0.6).contains(&y) && x.abs() <= (y + 0.65) * 0.6,
+            Self::Cross => x.abs() <= 0.7 && y.abs() <= 0.7 && ((x - y).abs() <= 0.3 || (x + y).abs() <= 0.3),
+            Self::Square => (0.4..=0.7).contains(&x.abs().max(y.abs())),
+            Self::Diamond => x.abs() + y.abs() <= 0.75,
+            Self::Plus => (x.abs() <= 0.2 && y.abs() <= 0.7) || (y.abs() <= 0.2 && x.abs() <= 0.7),
+        }
+    }
+
+    /// Returns a character that looks like the symbol, for drawing it as text.
+    pub fn glyph(self) -> char {
+        match self {
+            Self::Circle => '\u{25CF}',
+            Self::Triangle => '\u{25B2}',
+            Self::Cross => '\u{2716}',
+            Self::Square => '\u{25A0}',
+            Self::Diamond => '\u{25C6}',
+            Self::Plus => '\u{271A}',
+        }
+    }
+}
+
+
+/// The symbols of the block colors, in the same order as `BLOCK_COLORS`.
+pub(crate) const SYMBOLS: [Symbol; BLOCK_COLOR_COUNT] = [
+    Symbol::Circle, Symbol::Triangle, Symbol::Cross,
+    Symbol::Square, Symbol::Diamond, Symbol::Plus,
+];
+
+
+/// Returns black or white, whichever stands out more on the color.
+pub(crate) fn contrasting_color(color: Rgba) -> Rgba {
+    let luminance = 299 * u32::from(color.r) + 587 * u32::from(color.g) + 114 * u32::from(color.b);
+    if luminance >= 1000 * 0x80 {
+        Rgba::BLACK
+    } else {
+        Rgba::WHITE
+    }
+}
+
+
+/// Draws the symbol over the whole image, which consists of the pixels row by row from the top.
+pub(crate) fn draw_symbol(pixels: &mut [Rgba], width: u32, height: u32, symbol: Symbol, color: Rgba) {
+    let width_usize: usize = width.try_into().unwrap();
+    for y in 0..height {
+        let y_coord = f64::from(2*y + 1) / f64::from(height) - 1.0;
+        for x in 0..width {
+            let x_coord = f64::from(2*x + 1) / f64::from(width) - 1.0;
+            if symbol.covers(x_coord, y_coord) {
+                let index = usize::try_from(y).unwrap() * width_usize + usize::try_from(x).unwrap();
+                pixels[index] = color;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{SYMBOLS, draw_symbol};
+    use crate::render::Rgba;
+
+    #[test]
+    fn test_symbols_differ() {
+        let images: Vec<Vec<Rgba>> = SYMBOLS.iter()
+            .map(|&symbol| {
+                let mut pixels = vec![Rgba::BLACK; 25 * 25];
+                draw_symbol(&mut pixels, 25, 25, symbol, Rgba::WHITE);
+                pixels
+            })
+            .collect();
+        for (i, image) in images.iter().enumerate() {
+            assert!(image.contains(&Rgba::WHITE));
+            // the bevel around the symbol stays visible
+            assert_eq!(image[0], Rgba::BLACK);
+            for other in &images[i+1..] {
+                assert_ne!(image, other);
+            }
+        }
+    }
+}
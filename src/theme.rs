@@ -0,0 +1,347 @@
+//! Themes, which replace the generated graphics with images.
+//!
+//! A theme is a directory containing any of the following files, where each image may be a PNG or
+//! (with the extension `.bmp` instead of `.png`) an uncompressed bitmap:
+//!
+//! * `blocks.png`: a sprite sheet of square block images side by side. It starts with one image
+//!   for each of the six block colors, followed by the highlight shown on blocks of completed
+//!   sequences, optionally followed by one shadow image for each block color. Missing shadows are
+//!   darkened copies of the blocks.
+//! * `background.png`: drawn behind everything else, stretched to the size of the window.
+//! * `palette.txt`: the six block colors as hexadecimal `RRGGBB` codes (optionally prefixed with
+//!   `#`), one per line. They color the color statistics bars and, if the theme has no sprite
+//!   sheet, the generated blocks.
+//!
+//! Whatever the theme leaves out is drawn as without a theme.
+
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+use once_cell::sync::OnceCell;
+
+use crate::{BLOCK_COLOR_COUNT, BLOCK_COLORS, BLOCK_WIDTH_PX, COLOR_STATS_BAR_WIDTH, OPTS};
+use crate::clear_animation::ClearAnimation;
+use crate::render::{Renderer, Rgba};
+use crate::render::software::Image;
+use crate::symbols::{SYMBOLS, Symbol, contrasting_color, draw_symbol};
+
+
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+
+/// The built-in palettes, which replace the block colors of the theme.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, ValueEnum)]
+pub(crate) enum Palette {
+    /// Red, green, blue, yellow, cyan and magenta.
+    Standard,
+
+    /// The colorblind-safe palette by Okabe and Ito.
+    OkabeIto,
+
+    /// The colorblind-safe "bright" palette by Paul Tol.
+    TolBright,
+}
+impl Palette {
+    pub fn colors(self) -> [Rgba; BLOCK_COLOR_COUNT] {
+        // each in place of the most similar standard color
+        match self {
+            Self::Standard => BLOCK_COLORS,
+            Self::OkabeIto => [
+                Rgba::rgb(0xD5, 0x5E, 0x00), Rgba::rgb(0x00, 0x9E, 0x73), Rgba::rgb(0x00, 0x72, 0xB2),
+                Rgba::rgb(0xF0, 0xE4, 0x42), Rgba::rgb(0x56, 0xB4, 0xE9), Rgba::rgb(0xCC, 0x79, 0xA7),
+            ],
+            Self::TolBright => [
+                Rgba::rgb(0xEE, 0x66, 0x77), Rgba::rgb(0x22, 0x88, 0x33), Rgba::rgb(0x44, 0x77, 0xAA),
+                Rgba::rgb(0xCC, 0xBB, 0x44), Rgba::rgb(0x66, 0xCC, 0xEE), Rgba::rgb(0xAA, 0x33, 0x77),
+            ],
+        }
+    }
+}
+
+
+/// The images and colors of a theme.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Theme {
+    /// The block images in the sequence returned by `make_block_images`, if the theme has a
+    /// sprite sheet.
+    pub block_images: Option<Vec<Image>>,
+
+    pub background: Option<Image>,
+
+    pub palette: [Rgba; BLOCK_COLOR_COUNT],
+
+    /// Whether each block color is marked with its symbol.
+    pub symbols: bool,
+
+    pub clear_animation: ClearAnimation,
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            block_images: None,
+            background: None,
+            palette: BLOCK_COLORS,
+            symbols: false,
+            clear_animation: ClearAnimation::default(),
+        }
+    }
+}
+impl Theme {
+    /// Loads the theme from the files in the directory.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        if !dir.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("theme directory {} not found", dir.display())));
+        }
+
+        let block_images = load_image(dir, "blocks")?
+            .map(|sheet| split_sprite_sheet(&sheet))
+            .transpose()?;
+        let background = load_image(dir, "background")?;
+        let palette_path = dir.join("palette.txt");
+        let palette = if palette_path.exists() {
+            parse_palette(&fs::read_to_string(palette_path)?)?
+        } else {
+            BLOCK_COLORS
+        };
+        Ok(Self {
+            block_images,
+            background,
+            palette,
+            symbols: false,
+            clear_animation: ClearAnimation::default(),
+        })
+    }
+}
+
+
+/// Loads the theme chosen in the options unless it has already been loaded, so that problems with
+/// it can be reported before the game is shown.
+pub(crate) fn load_current() -> io::Result<&'static Theme> {
+    THEME.get_or_try_init(|| {
+        let opts = OPTS.get().expect("OPTS not set?!");
+        let mut theme = match &opts.theme {
+            Some(dir) => Theme::load(dir)?,
+            None => Theme::default(),
+        };
+        if let Some(palette) = opts.palette {
+            theme.palette = palette.colors();
+        }
+        theme.symbols = opts.color_symbols;
+        theme.clear_animation = opts.clear_animation();
+        Ok(theme)
+    })
+}
+
+
+/// Returns the theme chosen in the options, along with the palette, symbols and clear animation
+/// chosen there, loading it on first use.
+pub(crate) fn current() -> &'static Theme {
+    load_current().expect("failed to load theme")
+}
+
+
+/// Loads the PNG or bitmap with the given name from the directory, if there is one.
+fn load_image(dir: &Path, name: &str) -> io::Result<Option<Image>> {
+    for extension in ["png", "bmp"] {
+        let path = dir.join(format!("{}.{}", name, extension));
+        if path.exists() {
+            return Image::load(&path)
+                .map(Some)
+                .map_err(|e| io::Error::new(e.kind(), format!("failed to load {}: {}", path.display(), e)));
+        }
+    }
+    Ok(None)
+}
+
+
+/// Cuts the sprite sheet into square block images and adds the shadows if they are missing.
+fn split_sprite_sheet(sheet: &Image) -> io::Result<Vec<Image>> {
+    let size = sheet.height;
+    let cell_count = if size > 0 && sheet.width % size == 0 { sheet.width / size } else { 0 };
+    let with_shadows = u32::try_from(2*BLOCK_COLOR_COUNT + 1).unwrap();
+    let without_shadows = u32::try_from(BLOCK_COLOR_COUNT + 1).unwrap();
+    if cell_count != with_shadows && cell_count != without_shadows {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the block sprite sheet must consist of {} or {} square images side by side", without_shadows, with_shadows),
+        ));
+    }
+
+    let mut images: Vec<Image> = (0..cell_count)
+        .map(|i| sheet.crop(i * size, 0, size, size))
+        .collect();
+    if cell_count == without_shadows {
+        for i in 0..BLOCK_COLOR_COUNT {
+            let mut shadow = images[i].clone();
+            for pixel in &mut shadow.pixels {
+                *pixel = Rgba::new(pixel.r / 3, pixel.g / 3, pixel.b / 3, pixel.a);
+            }
+            images.push(shadow);
+        }
+    }
+    Ok(images)
+}
+
+
+fn parse_palette(text: &str) -> io::Result<[Rgba; BLOCK_COLOR_COUNT]> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut colors = Vec::with_capacity(BLOCK_COLOR_COUNT);
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let hex = line.strip_prefix('#').unwrap_or(line);
+        let value = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or_else(|| invalid(format!("invalid palette color {:?}", line)))?;
+        let [_, r, g, b] = value.to_be_bytes();
+        colors.push(Rgba::rgb(r, g, b));
+    }
+    colors.try_into()
+        .map_err(|colors: Vec<Rgba>| invalid(format!("the palette must have {} colors, not {}", BLOCK_COLOR_COUNT, colors.len())))
+}
+
+
+/// The sprites and colors of a theme, prepared for drawing with a renderer.
+pub(crate) struct ThemeSprites<S> {
+    /// The block sprites in the sequence returned by `make_block_images`.
+    pub blocks: Vec<S>,
+
+    pub background: Option<S>,
+
+    pub palette: [Rgba; BLOCK_COLOR_COUNT],
+
+    /// The symbol of each block color in that color, if the theme shows symbols.
+    pub symbols: Vec<S>,
+
+    pub clear_animation: ClearAnimation,
+
+    /// For each block color, the sprites that the clear animation shows in turn, if it consists
+    /// of sprites.
+    pub clear_stages: Vec<Vec<S>>,
+}
+impl<S> ThemeSprites<S> {
+    /// Creates the sprites of the theme; generated blocks get the given size.
+    pub fn new<R: Renderer<Sprite = S>>(renderer: &mut R, theme: &Theme, block_width: u32, block_height: u32) -> Self {
+        let images = match &theme.block_images {
+            Some(images) => {
+                let mut images = images.clone();
+                if theme.symbols {
+                    add_symbols(&mut images);
+                }
+                images
+            },
+            None => crate::make_block_images(&theme.palette, theme.symbols, block_width, block_height),
+        };
+        let blocks = images.iter()
+            .map(|image| renderer.create_sprite(image.width, image.height, &image.pixels))
+            .collect();
+        let clear_stages = images[..BLOCK_COLOR_COUNT].iter()
+            .map(|image| {
+                theme.clear_animation.stage_images(image).iter()
+                    .map(|stage| renderer.create_sprite(stage.width, stage.height, &stage.pixels))
+                    .collect()
+            })
+            .collect();
+        let background = theme.background.as_ref()
+            .map(|image| renderer.create_sprite(image.width, image.height, &image.pixels));
+
+        let mut symbols = Vec::new();
+        if theme.symbols {
+            // as sharp as the scaled color statistics bars they label
+            let size = (block_width * COLOR_STATS_BAR_WIDTH / BLOCK_WIDTH_PX).max(1);
+            for (&symbol, &color) in SYMBOLS.iter().zip(&theme.palette) {
+                let mut pixels = Image::new(size, size, Rgba::BLACK.with_alpha(0)).pixels;
+                draw_symbol(&mut pixels, size, size, symbol, color);
+                symbols.push(renderer.create_sprite(size, size, &pixels));
+            }
+        }
+
+        Self {
+            blocks,
+            background,
+            palette: theme.palette,
+            symbols,
+            clear_animation: theme.clear_animation,
+            clear_stages,
+        }
+    }
+}
+
+
+/// Draws the symbols onto the block images of a sprite sheet and onto their shadows, within the
+/// visible parts of the images.
+fn add_symbols(images: &mut [Image]) {
+    for (i, &symbol) in SYMBOLS.iter().enumerate() {
+        let color = average_color(&images[i]);
+        draw_symbol_within(&mut images[i], symbol, contrasting_color(color));
+        draw_symbol_within(&mut images[BLOCK_COLOR_COUNT + 1 + i], symbol, color);
+    }
+}
+
+
+fn draw_symbol_within(image: &mut Image, symbol: Symbol, color: Rgba) {
+    let original = image.pixels.clone();
+    draw_symbol(&mut image.pixels, image.width, image.height, symbol, color);
+    for (pixel, original_pixel) in image.pixels.iter_mut().zip(original) {
+        pixel.a = original_pixel.a;
+    }
+}
+
+
+/// Returns the average color of the visible pixels of the image.
+fn average_color(image: &Image) -> Rgba {
+    let mut sums = [0u64; 3];
+    let mut count = 0u64;
+    for pixel in image.pixels.iter().filter(|p| p.a >= 0x80) {
+        sums[0] += u64::from(pixel.r);
+        sums[1] += u64::from(pixel.g);
+        sums[2] += u64::from(pixel.b);
+        count += 1;
+    }
+    if count == 0 {
+        return Rgba::BLACK;
+    }
+    let [r, g, b] = sums.map(|sum| u8::try_from(sum / count).unwrap());
+    Rgba::rgb(r, g, b)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Theme;
+    use crate::BLOCK_COLORS;
+    use crate::render::Rgba;
+    use crate::render::software::Image;
+    use std::fs;
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("columns-theme-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // seven 2x2 cells: the blocks and the highlight, without shadows
+        let mut sheet = Image::new(14, 2, Rgba::rgb(0x30, 0x60, 0x90));
+        sheet.pixels[12] = Rgba::WHITE;
+        sheet.write_bmp(fs::File::create(dir.join("blocks.bmp")).unwrap()).unwrap();
+        fs::write(dir.join("palette.txt"), "#112233\n445566\n778899\naabbcc\nddeeff\n000000\n").unwrap();
+
+        let theme = Theme::load(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let block_images = theme.block_images.unwrap();
+        assert_eq!(block_images.len(), 13);
+        assert_eq!(block_images[6].pixel(0, 0), Rgba::WHITE);
+        assert_eq!(block_images[0].pixel(1, 1), Rgba::rgb(0x30, 0x60, 0x90));
+        assert_eq!(block_images[7].pixel(1, 1), Rgba::rgb(0x10, 0x20, 0x30));
+        assert_eq!(theme.background, None);
+        assert_eq!(theme.palette[0], Rgba::rgb(0x11, 0x22, 0x33));
+        assert_ne!(theme.palette, BLOCK_COLORS);
+    }
+}
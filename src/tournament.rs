@@ -0,0 +1,248 @@
+//! Tournaments between AI configurations.
+//!
+//! Every contestant plays one headless game on each of the same seeds, so all contestants get
+//! exactly the same pieces as long as they place them in the same way. Contestants are given as:
+//!
+//! * `greedy`, `mcts` or `adaptive`: a built-in strategy (the adaptive strategy uses the default
+//!   danger thresholds)
+//! * `weights:FILE`: the greedy strategy, but rating fields by a weighted sum of the criteria (see
+//!   [`crate::ai::weights`] for the file format)
+//! * `bot:CMD`: an external bot started with the given command line (see [`crate::bot`])
+//!
+//! The contestants are ranked by their mean score along with a 95% confidence interval (using the
+//! normal approximation). The head-to-head table counts on how many seeds each contestant scored
+//! more than each other contestant.
+
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::ai::{AiStrategy, BestMove};
+use crate::ai::adaptive::DangerThresholds;
+use crate::ai::weights::Weights;
+use crate::bot::Bot;
+use crate::game::{Game, GameEvent, ai_rng_from_seed};
+use crate::model::Field;
+
+
+/// The quantile of the standard normal distribution for a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+
+/// How a contestant picks its moves.
+enum Contestant {
+    Strategy(AiStrategy),
+    Weights(Weights),
+    Bot(String),
+}
+impl Contestant {
+    fn from_spec(spec: &str) -> io::Result<Self> {
+        if let Some(path) = spec.strip_prefix("weights:") {
+            Ok(Self::Weights(Weights::read(Path::new(path))?))
+        } else if let Some(command_line) = spec.strip_prefix("bot:") {
+            Ok(Self::Bot(command_line.to_owned()))
+        } else {
+            AiStrategy::from_str(spec, true)
+                .map(Self::Strategy)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown contestant {:?}", spec)))
+        }
+    }
+}
+
+
+/// The parameters of a tournament.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Settings {
+    /// How many games each contestant plays.
+    pub games: usize,
+
+    /// The seed of the first game; the following games use the subsequent seeds.
+    pub first_seed: u128,
+
+    /// Games are ended once this many pieces have been placed.
+    pub max_pieces: u64,
+
+    /// How much time the MCTS strategy may spend per move.
+    pub time_budget: Duration,
+
+    /// How many games are played at the same time (except by bots, which play one game at a time).
+    pub thread_count: usize,
+}
+
+
+/// Plays a game headlessly, asking the function for each move, and returns the final score.
+fn play_game<F: FnMut(&Field) -> Option<BestMove>>(seed: u128, max_pieces: u64, mut pick_best_move: F) -> u64 {
+    let mut game = Game::new(seed);
+    for _ in 0..max_pieces {
+        if game.run_until_next_piece() != Some(GameEvent::PieceSpawned) {
+            break;
+        }
+        if let Some(best_move) = pick_best_move(&game.field) {
+            game.apply_move(&best_move);
+        }
+        game.drop_piece();
+    }
+    game.score
+}
+
+
+/// Plays one game per seed and returns the scores in order of the seeds.
+fn play_games(contestant: &Contestant, seeds: &[u128], settings: &Settings) -> io::Result<Vec<u64>> {
+    match contestant {
+        Contestant::Strategy(AiStrategy::Greedy) => Ok(crate::ai::parallel_map(seeds, settings.thread_count, |&seed| {
+            play_game(seed, settings.max_pieces, |field| crate::ai::pick_best_move(field, 1))
+        })),
+        Contestant::Strategy(AiStrategy::Mcts) => Ok(crate::ai::parallel_map(seeds, settings.thread_count, |&seed| {
+            let mut ai_rng = ai_rng_from_seed(seed);
+            play_game(seed, settings.max_pieces, |field| {
+                crate::ai::mcts::pick_best_move(field, settings.time_budget, 1, &mut ai_rng)
+            })
+        })),
+        Contestant::Strategy(AiStrategy::Adaptive) => Ok(crate::ai::parallel_map(seeds, settings.thread_count, |&seed| {
+            let thresholds = DangerThresholds::default();
+            play_game(seed, settings.max_pieces, |field| crate::ai::adaptive::pick_best_move(field, &thresholds, 1))
+        })),
+        Contestant::Weights(weights) => Ok(crate::ai::parallel_map(seeds, settings.thread_count, |&seed| {
+            play_game(seed, settings.max_pieces, |field| crate::ai::weights::pick_best_move(field, weights, 1))
+        })),
+        Contestant::Bot(command_line) => {
+            let mut bot = Bot::spawn(command_line)?;
+            let mut scores = Vec::with_capacity(seeds.len());
+            for &seed in seeds {
+                let mut error = None;
+                let score = play_game(seed, settings.max_pieces, |field| {
+                    match bot.pick_best_move(field) {
+                        Ok(best_move) => Some(best_move),
+                        Err(e) => {
+                            error.get_or_insert(e);
+                            None
+                        },
+                    }
+                });
+                if let Some(e) = error {
+                    return Err(e);
+                }
+                scores.push(score);
+            }
+            Ok(scores)
+        },
+    }
+}
+
+
+/// Returns the mean of the scores along with the half-width of its 95% confidence interval.
+fn mean_with_confidence(scores: &[u64]) -> (f64, f64) {
+    if scores.is_empty() {
+        return (0.0, 0.0);
+    }
+    let count = scores.len() as f64;
+    let mean = scores.iter().map(|&s| s as f64).sum::<f64>() / count;
+    if scores.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = scores.iter()
+        .map(|&s| (s as f64 - mean).powi(2))
+        .sum::<f64>() / (count - 1.0);
+    (mean, Z_95 * (variance / count).sqrt())
+}
+
+
+/// Adds the contestant that caused it to the error.
+fn contestant_error(spec: &str, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", spec, error))
+}
+
+
+/// Plays the tournament between the contestants and prints the results on standard output.
+pub(crate) fn run(specs: &[String], settings: &Settings) -> io::Result<()> {
+    let contestants: Vec<Contestant> = specs.iter()
+        .map(|spec| Contestant::from_spec(spec).map_err(|e| contestant_error(spec, e)))
+        .collect::<io::Result<_>>()?;
+    let seeds: Vec<u128> = (0..settings.games)
+        .map(|i| settings.first_seed.wrapping_add(u128::try_from(i).unwrap()))
+        .collect();
+
+    let mut scores = Vec::with_capacity(contestants.len());
+    for (spec, contestant) in specs.iter().zip(&contestants) {
+        eprintln!("playing {} games as {}", seeds.len(), spec);
+        let contestant_scores = play_games(contestant, &seeds, settings)
+            .map_err(|e| contestant_error(spec, e))?;
+        scores.push(contestant_scores);
+    }
+
+    // number the contestants to refer to them in the head-to-head table
+    let labels: Vec<String> = specs.iter()
+        .enumerate()
+        .map(|(i, spec)| format!("{} {}", i + 1, spec))
+        .collect();
+    let name_width = labels.iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("contestant".len());
+
+    // ranking
+    let mut ranking: Vec<(usize, f64, f64)> = scores.iter()
+        .enumerate()
+        .map(|(i, contestant_scores)| {
+            let (mean, half_width) = mean_with_confidence(contestant_scores);
+            (i, mean, half_width)
+        })
+        .collect();
+    ranking.sort_by(|(_, mean_a, _), (_, mean_b, _)| mean_b.total_cmp(mean_a));
+    println!("rank  {:name_width$}  {:>10}  {:>21}", "contestant", "mean score", "95% CI", name_width = name_width);
+    for (rank, (i, mean, half_width)) in ranking.iter().enumerate() {
+        let interval = format!("[{:.1}, {:.1}]", mean - half_width, mean + half_width);
+        println!(
+            "{:>4}  {:name_width$}  {:>10.1}  {:>21}",
+            rank + 1, labels[*i], mean, interval, name_width = name_width,
+        );
+    }
+    println!();
+
+    // head-to-head wins
+    println!("wins of each row against each column on the same seed:");
+    print!("{:name_width$}", "", name_width = name_width);
+    for j in 0..contestants.len() {
+        print!("  {:>5}", j + 1);
+    }
+    println!();
+    for (i, label) in labels.iter().enumerate() {
+        print!("{:name_width$}", label, name_width = name_width);
+        for j in 0..contestants.len() {
+            if i == j {
+                print!("  {:>5}", "-");
+                continue;
+            }
+            let wins = scores[i].iter()
+                .zip(&scores[j])
+                .filter(|(score_i, score_j)| score_i > score_j)
+                .count();
+            print!("  {:>5}", wins);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::mean_with_confidence;
+
+    #[test]
+    fn test_mean_with_confidence() {
+        let (mean, half_width) = mean_with_confidence(&[2, 4, 4, 4, 5, 5, 7, 9]);
+        assert_eq!(mean, 5.0);
+
+        // sample standard deviation is sqrt(32/7)
+        let expected = 1.96 * (32.0f64 / 7.0 / 8.0).sqrt();
+        assert!((half_width - expected).abs() < 1e-9);
+
+        assert_eq!(mean_with_confidence(&[3]), (3.0, 0.0));
+    }
+}